@@ -0,0 +1,126 @@
+use rand::Rng;
+
+use crate::grid::{Grid, GridValueT};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BarrierLayout {
+    Empty,
+    //A bar through the middle of the grid covering the central half of its height
+    VerticalBar,
+    //Vertical walls with gaps alternating between the top and bottom of the grid
+    Maze,
+    //Square blocks scattered at random
+    RandomBlocks,
+}
+
+impl BarrierLayout {
+    pub fn from_name(name: &str) -> Option<BarrierLayout> {
+        match name {
+            "none" => Some(BarrierLayout::Empty),
+            "bar" => Some(BarrierLayout::VerticalBar),
+            "maze" => Some(BarrierLayout::Maze),
+            "blocks" => Some(BarrierLayout::RandomBlocks),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            BarrierLayout::Empty => "none",
+            BarrierLayout::VerticalBar => "bar",
+            BarrierLayout::Maze => "maze",
+            BarrierLayout::RandomBlocks => "blocks",
+        }
+    }
+
+    //Must be applied before any cells are placed
+    pub fn apply<R: Rng>(&self, grid: &mut Grid, rng: &mut R) {
+        let (width, height) = grid.get_dimensions();
+
+        match self {
+            BarrierLayout::Empty => {}
+            BarrierLayout::VerticalBar => {
+                let thickness = (width / 64).max(1);
+                let left = (width - thickness) / 2;
+
+                fill(grid, left, height / 4, thickness, height / 2);
+            }
+            BarrierLayout::Maze => {
+                const walls: usize = 4;
+
+                let gap = (height / 4).max(1);
+                for wall in 1..=walls {
+                    let x = wall * width / (walls + 1);
+                    //Odd walls are open at the top, even walls at the bottom
+                    let bottom = if wall % 2 == 1 { 0 } else { gap };
+
+                    fill(grid, x, bottom, 1, height - gap);
+                }
+            }
+            BarrierLayout::RandomBlocks => {
+                const blocks: usize = 12;
+
+                let size = (width.min(height) / 16).max(1);
+                for _ in 0..blocks {
+                    let x = rng.gen_range(0..=width - size);
+                    let y = rng.gen_range(0..=height - size);
+
+                    fill(grid, x, y, size, size);
+                }
+            }
+        }
+    }
+}
+
+fn fill(grid: &mut Grid, x: GridValueT, y: GridValueT, width: GridValueT, height: GridValueT) {
+    let (gridWidth, gridHeight) = grid.get_dimensions();
+
+    for cy in y..(y + height).min(gridHeight) {
+        for cx in x..(x + width).min(gridWidth) {
+            grid.set_barrier(cx, cy, true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BarrierLayout;
+    use crate::{
+        grid::Grid,
+        rng::{derive_rng, Stream},
+    };
+
+    #[test]
+    fn layouts_leave_room() {
+        for layout in [
+            BarrierLayout::Empty,
+            BarrierLayout::VerticalBar,
+            BarrierLayout::Maze,
+            BarrierLayout::RandomBlocks,
+        ] {
+            assert_eq!(BarrierLayout::from_name(layout.get_name()), Some(layout));
+
+            let mut grid = Grid::new(64, 48);
+            layout.apply(&mut grid, &mut derive_rng(0, Stream::Barriers, 0, 0, 0));
+
+            let barriers = grid.get_barrier_coords().len();
+            assert_eq!(grid.get_free_count(), 64 * 48 - barriers);
+            assert_eq!(barriers == 0, layout == BarrierLayout::Empty);
+
+            grid.reset();
+            assert_eq!(grid.get_barrier_coords().len(), barriers);
+        }
+    }
+
+    #[test]
+    fn barriers_block_movement() {
+        let mut grid = Grid::new(8, 8);
+        grid.set_barrier(3, 3, true);
+
+        assert!(!grid.is_free(3, 3));
+        assert!(grid.is_blocked(3, 3));
+        assert_eq!(grid.dist_to_occupied((0, 3), (1, 0), 8), None);
+        assert_eq!(grid.dist_to_wall((0, 3), (1, 0)), 3);
+        assert_eq!(grid.dist_to_wall((0, 4), (1, 0)), 8);
+    }
+}
@@ -9,11 +9,12 @@ use scoped_threadpool::Pool;
 
 use crate::cell::HeritableData;
 use crate::computeMovements;
-use crate::determine_deaths;
-use crate::determine_reproducers;
 use crate::gene::Gene;
 use crate::grid::Grid;
 use crate::population::Population;
+use crate::rng::{derive_rng, Stream};
+use crate::simulation::determine_deaths;
+use crate::simulation::determine_reproducers;
 use crate::DebugCell::DebugRefCell;
 
 use super::Config;
@@ -30,6 +31,7 @@ fn normal_setup() -> (Config, Rc<DebugRefCell<Grid>>, Rc<DebugRefCell<Population
     let population = Rc::new(DebugRefCell::new(Population::new(
         &config,
         &mut grid.borrowMut(),
+        &mut derive_rng(config.get_seed(), Stream::Genesis, 0, 0, 0),
     )));
 
     (config, grid, population)
@@ -37,10 +39,14 @@ fn normal_setup() -> (Config, Rc<DebugRefCell<Grid>>, Rc<DebugRefCell<Population
 
 #[bench]
 fn assignGrid(b: &mut Bencher) {
-    let (_config, grid, population) = normal_setup();
+    let (config, grid, population) = normal_setup();
+
+    let mut rng = derive_rng(config.get_seed(), Stream::Placement, 0, 0, 0);
 
     b.iter(|| {
-        population.borrowMut().assignRandom(&mut grid.borrowMut());
+        population
+            .borrowMut()
+            .assignRandom(&mut grid.borrowMut(), &mut rng);
         grid.borrowMut().reset();
     });
 }
@@ -49,21 +55,32 @@ fn assignGrid(b: &mut Bencher) {
 fn genRandom(b: &mut Bencher) {
     let (config, grid, population) = normal_setup();
 
+    let mut rng = derive_rng(config.get_seed(), Stream::Genesis, 0, 0, 0);
+
     b.iter(|| {
         population
             .borrowMut()
-            .genRandom(&config, &mut grid.borrowMut());
+            .genRandom(&config, &mut grid.borrowMut(), &mut rng);
         grid.borrowMut().reset();
     });
 }
 
 #[bench]
 fn computeMovementsBench(b: &mut Bencher) {
-    let (config, _grid, population) = normal_setup();
+    let (config, grid, population) = normal_setup();
 
     let mut threadpool = Pool::new(std::thread::available_parallelism().unwrap().get() as u32);
 
-    b.iter(|| computeMovements(&config, &mut threadpool, &mut population.borrowMut(), 0));
+    b.iter(|| {
+        computeMovements(
+            &config,
+            &mut threadpool,
+            &mut population.borrowMut(),
+            &grid.borrow(),
+            0,
+            0,
+        )
+    });
 }
 
 #[bench]
@@ -78,6 +95,8 @@ fn reproduceAsexually(b: &mut Bencher) {
     //Safety: this will be written to before it is ever read from
     let mut scratch = { unsafe { scratch.assume_init() } };
 
+    let mut rng = derive_rng(config.get_seed(), Stream::Reproduction, 0, 0, 0);
+
     b.iter(|| {
         let reproducing_cells = population.borrow().getLivingIndices();
         grid.borrowMut().reset();
@@ -86,6 +105,7 @@ fn reproduceAsexually(b: &mut Bencher) {
             &config,
             reproducing_cells,
             &mut grid.borrowMut(),
+            &mut rng,
         )
     });
 }
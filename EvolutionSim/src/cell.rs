@@ -1,13 +1,17 @@
-use std::backtrace::Backtrace;
+use std::{
+    backtrace::Backtrace,
+    io::{self, Read, Write},
+    mem::size_of,
+};
 
 use custom_dst::{DstData, MaybeUninitDstArray};
-use rand::{rngs::ThreadRng, thread_rng, Rng};
+use rand::Rng;
 
 use crate::{
     config::MutR,
     gene::{Gene, NodeID},
-    grid::GridValueT,
-    neuron::NeuralNet,
+    grid::{Grid, GridValueT},
+    neuron::{Activation, NeuralNet},
     TimeT,
 };
 
@@ -66,104 +70,197 @@ impl MiscData {
 #[derive(Clone, Copy, Debug, Default)]
 pub struct HeritableData {
     oscillatorPeriod: TimeT,
+    //Only used when the config lets cells inherit their activations
+    innerActivation: Activation,
+    outputActivation: Activation,
 }
 
 impl HeritableData {
+    //Activations are only drawn when they are inherited, so runs without it use the same random
+    //numbers as before they existed
+    pub fn new_random<R: Rng>(
+        rng: &mut R,
+        steps_per_gen: TimeT,
+        inherit_activation: bool,
+    ) -> HeritableData {
+        let mut data = HeritableData {
+            oscillatorPeriod: normalize_oscillator(rng.gen(), steps_per_gen),
+            ..HeritableData::default()
+        };
+
+        if inherit_activation {
+            data.innerActivation = Activation::get_random(rng);
+            data.outputActivation = Activation::get_random(rng);
+        }
+
+        data
+    }
+
     pub fn get_oscillator(&self) -> usize {
         self.oscillatorPeriod
     }
+
+    //Inner and output activations
+    pub fn get_activations(&self) -> (Activation, Activation) {
+        (self.innerActivation, self.outputActivation)
+    }
+
+    pub fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_all(&(self.oscillatorPeriod as u64).to_le_bytes())?;
+        writer.write_all(&[
+            self.innerActivation.get_index() as u8,
+            self.outputActivation.get_index() as u8,
+        ])
+    }
+
+    pub fn deserialize<T: Read>(reader: &mut T, steps_per_gen: TimeT) -> io::Result<Self> {
+        let mut buf8 = [0; size_of::<u64>()];
+        reader.read_exact(&mut buf8)?;
+        let mut activations = [0; 2];
+        reader.read_exact(&mut activations)?;
+
+        let activation = |index: u8| {
+            Activation::from_index(index as usize).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid activation {}", index),
+                )
+            })
+        };
+
+        Ok(HeritableData {
+            oscillatorPeriod: normalize_oscillator(
+                u64::from_le_bytes(buf8) as TimeT,
+                steps_per_gen,
+            ),
+            innerActivation: activation(activations[0])?,
+            outputActivation: activation(activations[1])?,
+        })
+    }
+
+    //Each activation comes from either parent with equal chance
+    pub fn cross_activations<R: Rng>(
+        &mut self,
+        first: &HeritableData,
+        second: &HeritableData,
+        rng: &mut R,
+    ) {
+        self.innerActivation = if rng.gen_bool(0.5) {
+            first.innerActivation
+        } else {
+            second.innerActivation
+        };
+        self.outputActivation = if rng.gen_bool(0.5) {
+            first.outputActivation
+        } else {
+            second.outputActivation
+        };
+    }
+
+    //Each activation is replaced by a random one with the mutation rate as the percent chance
+    pub fn mutate_activations<R: Rng>(&mut self, mutationRate: MutR, rng: &mut R) {
+        for activation in [&mut self.innerActivation, &mut self.outputActivation] {
+            if rng.gen_range(0.0f32..100.0) < mutationRate {
+                *activation = Activation::get_random(rng);
+            }
+        }
+    }
 }
 
 fn normalize_oscillator(period: TimeT, steps_per_gen: TimeT) -> TimeT {
     period % steps_per_gen
 }
 
-pub fn gen_random_other(
+pub fn gen_random_other<R: Rng>(
     other_data: &mut DstData<HeritableData, Gene>,
-    rng: &mut ThreadRng,
+    rng: &mut R,
     steps_per_gen: TimeT,
+    inner_nodes: usize,
+    inherit_activation: bool,
 ) {
     for gene in other_data.get_mut_footer() {
-        *gene = Gene::new_random(rng);
+        *gene = Gene::new_random(rng, inner_nodes);
     }
 
-    *other_data.get_header_mut() = HeritableData {
-        oscillatorPeriod: normalize_oscillator(rng.gen(), steps_per_gen),
-    }
+    *other_data.get_header_mut() =
+        HeritableData::new_random(rng, steps_per_gen, inherit_activation);
 }
 
-pub unsafe fn write_random_other_init(
+pub(crate) unsafe fn write_random_other_init<R: Rng>(
     array: &mut MaybeUninitDstArray<HeritableData, Gene>,
     arr_index: usize,
-    rng: &mut ThreadRng,
+    rng: &mut R,
     genome_length: usize,
     steps_per_gen: TimeT,
-) {
+    inner_nodes: usize,
+    inherit_activation: bool,
+) -> HeritableData {
     let mut gene_ptr = array.get_footer_element_ptr_mut(arr_index, 0);
 
     //SAFETY we know the size of the footer and we know the arr index, so this is safe
     for _ in 0..genome_length {
         unsafe {
-            *gene_ptr = Gene::new_random(rng);
+            *gene_ptr = Gene::new_random(rng, inner_nodes);
             gene_ptr = gene_ptr.add(1);
         }
     }
 
-    array.write_header(
-        arr_index,
-        HeritableData {
-            oscillatorPeriod: normalize_oscillator(rng.gen(), steps_per_gen),
-        },
-    );
+    let header = HeritableData::new_random(rng, steps_per_gen, inherit_activation);
+    array.write_header(arr_index, header);
+
+    header
 }
 
-#[allow(unused)]
-pub fn sexuallyReproduce(
+//Uniform crossover, each gene and the oscillator come from either parent with equal chance
+//
+//Activations are left to the caller, as they are only crossed over when they are inherited
+pub fn sexuallyReproduce<R: Rng>(
     heritable_data_1: &DstData<HeritableData, Gene>,
     heritable_data_2: &DstData<HeritableData, Gene>,
     cell_loc: &mut DstData<HeritableData, Gene>,
     stepsPerGen: TimeT,
     mutationRate: MutR,
+    innerNodes: usize,
+    rng: &mut R,
 ) {
-    let mut rng = thread_rng();
-
     for (index, gene) in cell_loc.get_mut_footer().iter_mut().enumerate() {
-        if rng.gen_bool(0.5) {
-            if rng.gen_range(0.0..100.0f32) < mutationRate {
-                let bit = rng.gen_range(0..32u32);
+        *gene = if rng.gen_bool(0.5) {
+            heritable_data_1.get_footer()[index]
+        } else {
+            heritable_data_2.get_footer()[index]
+        };
 
-                *gene = heritable_data_1.get_footer()[index] ^ (1 << (bit & 31));
-            }
-        } else if rng.gen_range(0.0..100.0f32) < mutationRate {
+        if rng.gen_range(0.0f32..100.0) < mutationRate {
             let bit = rng.gen_range(0..32u32);
-            *gene = heritable_data_2.get_footer()[index] ^ (1 << (bit & 31));
+
+            *gene = Gene::new(gene.gene ^ (1 << (bit & 31)), innerNodes);
         }
     }
 
     let oscillator = &mut cell_loc.get_header_mut().oscillatorPeriod;
 
-    if thread_rng().gen_bool(0.5) {
+    if rng.gen_bool(0.5) {
         *oscillator = heritable_data_1.get_header().oscillatorPeriod;
     } else {
         *oscillator = heritable_data_2.get_header().oscillatorPeriod;
     }
 
     if rng.gen_range(0.0f32..100.0) < mutationRate {
-        let bit = thread_rng().gen_range(0..32u32);
-        *oscillator ^= (1 << (bit & 31));
+        let bit = rng.gen_range(0..32u32);
+        *oscillator ^= 1 << (bit & 31);
     }
 
     *oscillator = normalize_oscillator(*oscillator, stepsPerGen);
 }
 
-pub fn asexuallyReproduce(
+pub fn asexuallyReproduce<R: Rng>(
     heritable_data: &DstData<HeritableData, Gene>,
     cell_loc: &mut DstData<HeritableData, Gene>,
     stepsPerGen: TimeT,
     mutationRate: MutR,
+    innerNodes: usize,
+    rng: &mut R,
 ) {
-    let mut rng = thread_rng();
-
     //Bitwise copy of the cell, is currently valid
     *cell_loc.get_header_mut() = *heritable_data.get_header();
     cell_loc
@@ -174,7 +271,7 @@ pub fn asexuallyReproduce(
         if rng.gen_range(0.0f32..100.0) < mutationRate {
             let bit = rng.gen_range(0..32u32);
 
-            *i = Gene::new(i.gene ^ (1 << (bit & 31)));
+            *i = Gene::new(i.gene ^ (1 << (bit & 31)), innerNodes);
         }
     }
 
@@ -188,21 +285,36 @@ pub fn asexuallyReproduce(
     *oscillator = normalize_oscillator(*oscillator, stepsPerGen);
 }
 
-pub fn one_step(
+pub fn one_step<R: Rng>(
     neuron_data: &mut NeuronData,
     movement_data: &MovementData,
     oscillator: TimeT,
     step: TimeT,
-    gridWidth: GridValueT,
-    gridHeight: GridValueT,
+    grid: &Grid,
     stepsPerGen: TimeT,
-    rng: &mut ThreadRng,
-) -> (usize, usize) {
+    densityRadius: f32,
+    sightDistance: GridValueT,
+    killEnabled: bool,
+    rng: &mut R,
+) -> ((usize, usize), bool) {
+    let (gridWidth, gridHeight) = grid.get_dimensions();
+
+    let coords = movement_data.getCoords();
+    let forward = {
+        let (dx, dy) = movement_data.lastMoveDir.get_move_offset();
+        (dx as isize, dy as isize)
+    };
+
     let values = [
         (2 * movement_data.x) as f32 / (gridWidth as f32) - 1.0,
         (2 * movement_data.y) as f32 / (gridHeight as f32) - 1.0,
         step as f32 / (stepsPerGen as f32),
         ((((((step as f32) / (oscillator as f32)) as i32) % 2) * 2) - 1) as f32,
+        grid.get_density(coords, densityRadius),
+        grid.is_blocked(coords.0 as isize + forward.0, coords.1 as isize + forward.1) as u8 as f32,
+        grid.dist_to_occupied(coords, forward, sightDistance)
+            .map_or(1.0, |distance| distance as f32 / sightDistance as f32),
+        grid.dist_to_wall(coords, forward).min(sightDistance) as f32 / sightDistance as f32,
     ];
     neuron_data.neural_net.prepare_net(&values);
     neuron_data.neural_net.feed_forward();
@@ -264,7 +376,13 @@ pub fn one_step(
         y = gridHeight - 1;
     }
 
-    (x, y)
+    let kill = killEnabled && {
+        let prob_kill = outputs[NodeID::get_output_index(&NodeID::KillForward)].tanh();
+
+        (rng.gen_range(0..i32::MAX) as f32) / (i32::MAX as f32) < prob_kill
+    };
+
+    ((x, y), kill)
 }
 
 pub fn createColor(genome: &[Gene]) -> (u8, u8, u8) {
@@ -325,7 +443,7 @@ impl Direction {
         }
     }
 
-    pub fn get_random(rng: &mut ThreadRng) -> Direction {
+    pub fn get_random<R: Rng>(rng: &mut R) -> Direction {
         match rng.gen_range(0..8) {
             0 => Direction::North,
             1 => Direction::NorthEast,
@@ -395,3 +513,51 @@ impl Direction {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::sexuallyReproduce;
+    use crate::{
+        config::Config,
+        grid::Grid,
+        population::Population,
+        rng::{derive_rng, Stream},
+    };
+
+    #[test]
+    fn crossover_takes_genes_from_parents() {
+        let parentConfig = Rc::new(Config::new(2, 64, 8, 8, 0.0, 30, false).unwrap());
+        let childConfig = Rc::new(Config::new(1, 64, 8, 8, 0.0, 30, false).unwrap());
+        let mut rng = derive_rng(1, Stream::Genesis, 0, 0, 0);
+
+        let parents = Population::new(&parentConfig, &mut Grid::new(8, 8), &mut rng);
+        let mut child = Population::new(&childConfig, &mut Grid::new(8, 8), &mut rng);
+
+        let (first, second) = (
+            parents.getCellHeritableData(0),
+            parents.getCellHeritableData(1),
+        );
+        let mut childData = child.get_mut_heritable_data();
+
+        sexuallyReproduce(first, second, &mut childData[0], 30, 0.0, 3, &mut rng);
+
+        let (mut fromFirst, mut fromSecond) = (0, 0);
+        for (index, gene) in childData[0].get_footer().iter().enumerate() {
+            if gene.gene == first.get_footer()[index].gene {
+                fromFirst += 1;
+            } else {
+                assert_eq!(gene.gene, second.get_footer()[index].gene);
+                fromSecond += 1;
+            }
+        }
+        assert!(fromFirst > 0 && fromSecond > 0);
+
+        let oscillator = childData[0].get_header().get_oscillator();
+        assert!(
+            oscillator == first.get_header().get_oscillator()
+                || oscillator == second.get_header().get_oscillator()
+        );
+    }
+}
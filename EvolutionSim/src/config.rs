@@ -1,25 +1,130 @@
 use std::{
-    fmt::Display,
-    io::{Read, Write},
+    fmt::{Display, Write as _},
+    io::{self, Read, Write},
     mem::size_of,
     process::exit,
 };
 
-use crate::{grid::GridValueT, TimeT};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    barrier::BarrierLayout,
+    frame::ImageFormat,
+    gene::{DEFAULT_INNER_NODE_COUNT, MAX_INNER_NODE_COUNT},
+    grid::GridValueT,
+    kill_schedule::KillSchedule,
+    neuron::Activation,
+    population::{Pairing, Reproduction},
+    selection::{self, SelectionCriterion},
+    TimeT,
+};
 
 pub type MutR = f32;
 
+//Exit code for invalid arguments or config files
+pub const EXIT_USAGE: i32 = 2;
+
+const DEFAULT_RECORD_DIR: &str = "recordings";
+
+//Settings stored in snapshots, in the order Config::serialize writes them
+const SNAPSHOT_KEYS: [&str; 8] = [
+    "population_size",
+    "genome_length",
+    "width",
+    "height",
+    "mutation_rate",
+    "steps_per_gen",
+    "seed",
+    "inner_nodes",
+];
+
+//A function so the limits come from their constants
+fn usage() -> String {
+    format!(
+        "Usage: EvolutionSim [options] [file=\"path\"]
+
+Options:
+  -p, --population-size <n>    Number of cells (default 4000)
+  -g, --genome-length <n>      Genes per cell (default 20)
+      --inner-nodes <n>        Inner neurons per cell, at most {} (default {})
+      --width <n>              Grid width (default 200)
+      --height <n>             Grid height (default 200)
+  -m, --mutation-rate <p>      Percent chance for each gene to mutate (default 0.1)
+  -s, --steps-per-gen <n>      Steps in a generation (default 250)
+      --seed <n>               Seed for the run, random if not given
+      --selection <spec>       Who reproduces, eg. \"or(edge(west, 0.25), edge(east, 0.25))\"
+      --kill-schedule <path>   File of kill zone events
+      --enable-kill            Let the KillForward output kill the cell ahead
+      --disable-kill           Ignore the KillForward output (default)
+      --density-radius <r>     Radius of the PopDensity sensor (default 2.5)
+      --sight-distance <n>     Length of the forward sensors (default 8)
+      --recurrent              Inner neurons keep their values between steps
+      --inner-decay <d>        Fraction of a recurrent inner neuron's value lost each step, 0 to 1 (default 0.5)
+      --inner-activation <f>   relu, tanh, sigmoid, identity or step for inner neurons (default relu)
+      --output-activation <f>  Same for output neurons (default identity)
+      --inherit-activation     Cells inherit their activations instead of using the two above
+      --barriers <layout>      none, bar, maze or blocks (default none)
+      --reproduction <mode>    asexual or sexual (default asexual)
+      --pairing <mode>         random or nearest partner for sexual reproduction (default random)
+      --save-interval <n>      Save every n generations, 0 disables (default 0)
+      --config <path>          Read settings from a file, flags override it
+      --stats <path>           Write per generation statistics, JSON Lines for .jsonl, otherwise CSV
+      --frames <dir>           Write images of the grid to a directory at the end of every generation, headless only
+      --frame-interval <n>     Also write an image every n steps, 0 disables (default 0)
+      --frame-format <format>  png or ppm (default png)
+      --record-gens <list>     Write every step of these generations as frames, eg. 0,100,500, headless only
+      --record-dir <dir>       Directory for recorded generations (default recordings)
+      --dot <path>             Write a cell's wiring as a Graphviz graph instead of running
+      --dot-cell <n>           Cell exported by --dot, from the saved generation if one is loaded (default 0)
+  -w                           Show the simulation in a window
+  file=\"path\"                  Resume from a saved generation, keeping the settings saved with it
+  -h, --help                   Print this message
+  -V, --version                Print the version
+
+Exit codes: 0 on success, 1 if the simulation fails, 2 for invalid options",
+        MAX_INNER_NODE_COUNT, DEFAULT_INNER_NODE_COUNT
+    )
+}
+
 pub struct Config {
     pop_size: usize,
     genome_length: usize,
+    inner_nodes: usize,
     grid_width: usize,
     grid_height: usize,
     mutation_rate: MutR,
     steps_per_gen: TimeT,
+    seed: u64,
+    selection: Box<dyn SelectionCriterion>,
+    kill_schedule: KillSchedule,
+    kill_enabled: bool,
+    density_radius: f32,
+    sight_distance: GridValueT,
+    recurrent: bool,
+    inner_decay: f32,
+    inner_activation: Activation,
+    output_activation: Activation,
+    inherit_activation: bool,
+    barriers: BarrierLayout,
+    reproduction: Reproduction,
+    pairing: Pairing,
     is_windowing: bool,
+    save_interval: usize,
+    load_path: Option<String>,
+    stats_path: Option<String>,
+    frames_path: Option<String>,
+    frame_interval: TimeT,
+    frame_format: ImageFormat,
+    record_gens: Vec<TimeT>,
+    record_dir: String,
+    dot_path: Option<String>,
+    dot_cell: usize,
+    //Snapshot settings given in a --config file, a resumed run checks them against the snapshot
+    file_snapshot_keys: Vec<&'static str>,
 }
 
 impl Config {
+    //Goes through the setters, so invalid values are errors rather than panics for library users
     pub fn new(
         pop_size: usize,
         genome_length: usize,
@@ -28,93 +133,342 @@ impl Config {
         mutation_rate: MutR,
         steps_per_gen: usize,
         is_windowing: bool,
-    ) -> Self {
-        assert!(pop_size <= (grid_width * grid_height));
-
-        Config {
-            pop_size,
-            genome_length,
-            grid_width,
-            grid_height,
-            mutation_rate,
-            steps_per_gen,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Config {
             is_windowing,
-        }
+            ..Config::default()
+        };
+
+        config.set_pop_size(pop_size)?;
+        config.set_genome_length(genome_length)?;
+        config.set_grid_width(grid_width)?;
+        config.set_grid_height(grid_height)?;
+        config.set_mutation_rate(mutation_rate)?;
+        config.set_steps_per_gen(steps_per_gen)?;
+        config.validate()?;
+
+        Ok(config)
     }
 
+    //Prints help, the version or the error and exits if the arguments don't describe a run
     pub fn initFromArgs() -> Self {
-        let mut config = Config::default();
+        //Drops naming, useless right now
+        let args: Vec<String> = std::env::args().skip(1).collect();
+
+        if args
+            .iter()
+            .any(|argument| argument == "-h" || argument == "--help")
+        {
+            println!("{}", usage());
+            exit(0);
+        } else if args
+            .iter()
+            .any(|argument| argument == "-V" || argument == "--version")
+        {
+            println!("EvolutionSim {}", env!("CARGO_PKG_VERSION"));
+            exit(0);
+        }
+
+        match Config::from_args(args) {
+            Ok(config) => config,
+            Err(err) => {
+                println!(
+                    "Error: {}\nRun with --help to see the available options",
+                    err
+                );
+                exit(EXIT_USAGE);
+            }
+        }
+    }
+
+    pub fn from_args(args: Vec<String>) -> Result<Self, ConfigError> {
+        //The config file is read first so that flags override it
+        let mut config = match args.iter().position(|argument| argument == "--config") {
+            Some(index) => match args.get(index + 1) {
+                Some(path) => {
+                    Config::load_file(path).map_err(|message| ConfigError::InvalidFile {
+                        path: path.clone(),
+                        message,
+                    })?
+                }
+                None => return Err(ConfigError::MissingValue("--config".to_string())),
+            },
+            None => Config::default(),
+        };
 
         #[derive(PartialEq, Clone, Copy)]
         enum Next {
             PopSize,
             GenomeLength,
+            InnerNodes,
             GridWidth,
             GridHeight,
             MutationRate,
             StepsPerGen,
+            SaveInterval,
+            Seed,
+            Selection,
+            KillSchedule,
+            Reproduction,
+            Pairing,
+            DensityRadius,
+            SightDistance,
+            InnerDecay,
+            InnerActivation,
+            OutputActivation,
+            Barriers,
+            ConfigFile,
+            Stats,
+            Frames,
+            FrameInterval,
+            FrameFormat,
+            RecordGens,
+            RecordDir,
+            Dot,
+            DotCell,
         }
 
-        let mut next = None;
-
-        let mut args = std::env::args();
-        //Drops naming, useless right now
-        args.next();
+        //The option is kept for error messages
+        let mut next: Option<(Next, String)> = None;
+        //A snapshot brings its own values for the settings it stores, so these can't be given with file=
+        let mut snapshotOption: Option<String> = None;
 
         for argument in args {
-            match next {
-                Some(opt) => {
-                    match opt {
-                        Next::PopSize => config.set_pop_size(argument.parse::<usize>().unwrap()),
-                        Next::GenomeLength => {
-                            config.set_genome_length(argument.parse::<usize>().unwrap())
-                        }
-
-                        Next::GridWidth => {
-                            config.set_grid_width(argument.parse::<GridValueT>().unwrap())
-                        }
-                        Next::GridHeight => {
-                            config.set_grid_height(argument.parse::<GridValueT>().unwrap())
-                        }
-                        Next::MutationRate => {
-                            config.set_mutation_rate(argument.parse::<MutR>().unwrap())
-                        }
-                        Next::StepsPerGen => {
-                            config.set_steps_per_gen(argument.parse::<TimeT>().unwrap())
-                        }
+            match next.take() {
+                Some((opt, option)) => match opt {
+                    Next::PopSize => {
+                        config.set_pop_size(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
                     }
-                    next = None;
-                }
+                    Next::GenomeLength => {
+                        config.set_genome_length(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
+                    }
+                    Next::InnerNodes => {
+                        config.set_inner_nodes(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
+                    }
+                    Next::GridWidth => {
+                        config.set_grid_width(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
+                    }
+                    Next::GridHeight => {
+                        config.set_grid_height(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
+                    }
+                    Next::MutationRate => {
+                        config.set_mutation_rate(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
+                    }
+                    Next::StepsPerGen => {
+                        config.set_steps_per_gen(parse_number(&option, &argument)?)?;
+                        snapshotOption = Some(option);
+                    }
+                    Next::SaveInterval => {
+                        config.set_save_interval(parse_number(&option, &argument)?)
+                    }
+                    Next::Seed => {
+                        config.set_seed(parse_number(&option, &argument)?);
+                        snapshotOption = Some(option);
+                    }
+                    Next::Selection => config.set_selection(
+                        selection::parse_criterion(&argument)
+                            .map_err(|message| ConfigError::InvalidValue { option, message })?,
+                    ),
+                    Next::KillSchedule => config.set_kill_schedule(
+                        KillSchedule::load(&argument).map_err(|message| {
+                            ConfigError::InvalidFile {
+                                path: argument.clone(),
+                                message,
+                            }
+                        })?,
+                    ),
+                    Next::DensityRadius => {
+                        config.set_density_radius(parse_number(&option, &argument)?)?
+                    }
+                    Next::SightDistance => {
+                        config.set_sight_distance(parse_number(&option, &argument)?)?
+                    }
+                    Next::InnerDecay => {
+                        config.set_inner_decay(parse_number(&option, &argument)?)?
+                    }
+                    Next::InnerActivation => {
+                        config.inner_activation = parse_activation(option, &argument)?
+                    }
+                    Next::OutputActivation => {
+                        config.output_activation = parse_activation(option, &argument)?
+                    }
+                    Next::Barriers => config.set_barriers(
+                        BarrierLayout::from_name(&argument).ok_or_else(|| {
+                            ConfigError::InvalidValue {
+                                option,
+                                message: format!(
+                                    "Unknown barrier layout `{}`, expected none, bar, maze or blocks",
+                                    argument
+                                ),
+                            }
+                        })?,
+                    ),
+                    //Already loaded above
+                    Next::ConfigFile => {}
+                    Next::Stats => config.stats_path = Some(argument),
+                    Next::Frames => config.frames_path = Some(argument),
+                    Next::FrameInterval => {
+                        config.set_frame_interval(parse_number(&option, &argument)?)
+                    }
+                    Next::FrameFormat => config.set_frame_format(
+                        ImageFormat::from_name(&argument).ok_or_else(|| {
+                            ConfigError::InvalidValue {
+                                option,
+                                message: format!(
+                                    "Unknown image format `{}`, expected png or ppm",
+                                    argument
+                                ),
+                            }
+                        })?,
+                    ),
+                    Next::RecordGens => config.set_record_gens(
+                        parse_generations(&argument)
+                            .map_err(|message| ConfigError::InvalidValue { option, message })?,
+                    ),
+                    Next::RecordDir => config.record_dir = argument,
+                    Next::Dot => config.dot_path = Some(argument),
+                    Next::DotCell => config.dot_cell = parse_number(&option, &argument)?,
+                    Next::Reproduction => config.set_reproduction(
+                        Reproduction::from_name(&argument).ok_or_else(|| {
+                            ConfigError::InvalidValue {
+                                option,
+                                message: format!(
+                                    "Unknown reproduction mode `{}`, expected sexual or asexual",
+                                    argument
+                                ),
+                            }
+                        })?,
+                    ),
+                    Next::Pairing => config.set_pairing(Pairing::from_name(&argument).ok_or_else(
+                        || ConfigError::InvalidValue {
+                            option,
+                            message: format!(
+                                "Unknown pairing `{}`, expected random or nearest",
+                                argument
+                            ),
+                        },
+                    )?),
+                },
                 None => {
-                    if argument.eq("-w") {
+                    let opt = if argument.eq("-w") {
                         config.is_windowing = true;
+                        None
+                    } else if argument.eq("--enable-kill") {
+                        config.set_kill_enabled(true);
+                        None
+                    } else if argument.eq("--disable-kill") {
+                        config.set_kill_enabled(false);
+                        None
+                    } else if argument.eq("--recurrent") {
+                        config.set_recurrent(true);
+                        None
+                    } else if argument.eq("--inherit-activation") {
+                        config.set_inherit_activation(true);
+                        None
+                    } else if let Some(path) = argument.strip_prefix("file=") {
+                        config.load_path = Some(path.trim_matches('"').to_string());
+                        None
                     } else if argument.eq("--population-size") || argument.eq("-p") {
-                        next = Some(Next::PopSize)
+                        Some(Next::PopSize)
                     } else if argument.eq("--width") {
-                        next = Some(Next::GridWidth);
+                        Some(Next::GridWidth)
                     } else if argument.eq("--height") {
-                        next = Some(Next::GridHeight);
+                        Some(Next::GridHeight)
                     } else if argument.eq("-g") || argument.eq("--genome-length") {
-                        next = Some(Next::GenomeLength);
-                    } else if argument.eq("-m") || argument.eq("--mutatation-rate") {
-                        next = Some(Next::MutationRate);
+                        Some(Next::GenomeLength)
+                    } else if argument.eq("--inner-nodes") {
+                        Some(Next::InnerNodes)
+                    } else if argument.eq("-m") || argument.eq("--mutation-rate") {
+                        Some(Next::MutationRate)
+                    } else if argument.eq("--mutatation-rate") {
+                        println!("Warning: --mutatation-rate is deprecated, use --mutation-rate");
+                        Some(Next::MutationRate)
                     } else if argument.eq("-s") || argument.eq("--steps-per-gen") {
-                        next = Some(Next::StepsPerGen);
+                        Some(Next::StepsPerGen)
+                    } else if argument.eq("--save-interval") {
+                        Some(Next::SaveInterval)
+                    } else if argument.eq("--seed") {
+                        Some(Next::Seed)
+                    } else if argument.eq("--selection") {
+                        Some(Next::Selection)
+                    } else if argument.eq("--kill-schedule") {
+                        Some(Next::KillSchedule)
+                    } else if argument.eq("--density-radius") {
+                        Some(Next::DensityRadius)
+                    } else if argument.eq("--sight-distance") {
+                        Some(Next::SightDistance)
+                    } else if argument.eq("--inner-decay") {
+                        Some(Next::InnerDecay)
+                    } else if argument.eq("--inner-activation") {
+                        Some(Next::InnerActivation)
+                    } else if argument.eq("--output-activation") {
+                        Some(Next::OutputActivation)
+                    } else if argument.eq("--barriers") {
+                        Some(Next::Barriers)
+                    } else if argument.eq("--reproduction") {
+                        Some(Next::Reproduction)
+                    } else if argument.eq("--pairing") {
+                        Some(Next::Pairing)
+                    } else if argument.eq("--config") {
+                        Some(Next::ConfigFile)
+                    } else if argument.eq("--stats") {
+                        Some(Next::Stats)
+                    } else if argument.eq("--frames") {
+                        Some(Next::Frames)
+                    } else if argument.eq("--frame-interval") {
+                        Some(Next::FrameInterval)
+                    } else if argument.eq("--frame-format") {
+                        Some(Next::FrameFormat)
+                    } else if argument.eq("--record-gens") {
+                        Some(Next::RecordGens)
+                    } else if argument.eq("--record-dir") {
+                        Some(Next::RecordDir)
+                    } else if argument.eq("--dot") {
+                        Some(Next::Dot)
+                    } else if argument.eq("--dot-cell") {
+                        Some(Next::DotCell)
                     } else {
-                        panic!("Invalid Option");
-                    }
+                        return Err(ConfigError::UnknownOption(argument));
+                    };
+
+                    next = opt.map(|opt| (opt, argument));
                 }
             }
         }
 
-        if next.is_some() {
-            println!("Invalid Options\n");
-            exit(1);
+        if let Some((_, option)) = next {
+            return Err(ConfigError::MissingValue(option));
         }
 
-        assert!(config.pop_size <= (config.grid_width * config.grid_height));
+        if let (Some(_), Some(option)) = (&config.load_path, snapshotOption) {
+            return Err(ConfigError::InvalidValue {
+                option,
+                message: "A resumed run uses the value saved in the snapshot".to_string(),
+            });
+        }
+
+        config.validate()?;
 
-        config
+        Ok(config)
+    }
+
+    //Checks the settings that depend on each other
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let tiles = self.grid_width.saturating_mul(self.grid_height);
+        if self.pop_size > tiles {
+            return Err(ConfigError::TooDense {
+                pop_size: self.pop_size,
+                tiles,
+            });
+        }
+
+        Ok(())
     }
 
     pub fn get_pop_size(&self) -> usize {
@@ -125,6 +479,10 @@ impl Config {
         self.genome_length
     }
 
+    pub fn get_inner_nodes(&self) -> usize {
+        self.inner_nodes
+    }
+
     pub fn get_grid_width(&self) -> GridValueT {
         self.grid_width
     }
@@ -141,103 +499,606 @@ impl Config {
         self.steps_per_gen
     }
 
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_selection(&self) -> &dyn SelectionCriterion {
+        &*self.selection
+    }
+
+    pub fn get_kill_schedule(&self) -> &KillSchedule {
+        &self.kill_schedule
+    }
+
+    pub fn get_kill_enabled(&self) -> bool {
+        self.kill_enabled
+    }
+
+    pub fn get_density_radius(&self) -> f32 {
+        self.density_radius
+    }
+
+    pub fn get_sight_distance(&self) -> GridValueT {
+        self.sight_distance
+    }
+
+    pub fn get_recurrent(&self) -> bool {
+        self.recurrent
+    }
+
+    pub fn get_inner_decay(&self) -> f32 {
+        self.inner_decay
+    }
+
+    //The decay of recurrent inner neurons, None if they are cleared every step
+    pub fn get_inner_memory(&self) -> Option<f32> {
+        if self.recurrent {
+            Some(self.inner_decay)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_inner_activation(&self) -> Activation {
+        self.inner_activation
+    }
+
+    pub fn get_output_activation(&self) -> Activation {
+        self.output_activation
+    }
+
+    //Whether cells use the activations in their heritable data instead of the ones above
+    pub fn get_inherit_activation(&self) -> bool {
+        self.inherit_activation
+    }
+
+    pub fn get_barriers(&self) -> BarrierLayout {
+        self.barriers
+    }
+
+    pub fn get_reproduction(&self) -> Reproduction {
+        self.reproduction
+    }
+
+    pub fn get_pairing(&self) -> Pairing {
+        self.pairing
+    }
+
     pub fn get_is_windowing(&self) -> bool {
         self.is_windowing
     }
 
-    pub fn set_pop_size(&mut self, popSize: usize) {
-        debug_assert_ne!(popSize, 0);
+    pub fn get_save_interval(&self) -> usize {
+        self.save_interval
+    }
 
-        self.pop_size = popSize;
+    pub fn get_load_path(&self) -> Option<&str> {
+        self.load_path.as_deref()
     }
 
-    pub fn set_genome_length(&mut self, genomeLength: usize) {
-        debug_assert_ne!(genomeLength, 0);
+    pub fn get_stats_path(&self) -> Option<&str> {
+        self.stats_path.as_deref()
+    }
 
-        self.genome_length = genomeLength;
+    pub fn get_frames_path(&self) -> Option<&str> {
+        self.frames_path.as_deref()
     }
 
-    pub fn set_grid_width(&mut self, gridWidth: usize) {
-        debug_assert_ne!(gridWidth, 0);
+    pub fn get_frame_interval(&self) -> TimeT {
+        self.frame_interval
+    }
 
-        self.grid_width = gridWidth;
+    pub fn get_frame_format(&self) -> ImageFormat {
+        self.frame_format
     }
 
-    pub fn set_grid_height(&mut self, gridHeight: usize) {
-        debug_assert_ne!(gridHeight, 0);
+    pub fn is_recorded(&self, generation: TimeT) -> bool {
+        self.record_gens.binary_search(&generation).is_ok()
+    }
 
-        self.grid_height = gridHeight;
+    pub fn get_record_dir(&self) -> &str {
+        &self.record_dir
     }
 
-    pub fn set_steps_per_gen(&mut self, stepsPerGen: TimeT) {
-        debug_assert_ne!(stepsPerGen, 0);
+    pub fn get_dot_path(&self) -> Option<&str> {
+        self.dot_path.as_deref()
+    }
 
-        self.steps_per_gen = stepsPerGen;
+    pub fn get_dot_cell(&self) -> usize {
+        self.dot_cell
     }
 
-    pub fn set_mutation_rate(&mut self, mutationRate: MutR) {
-        debug_assert!(mutationRate >= 0.0);
+    pub fn set_pop_size(&mut self, popSize: usize) -> Result<(), ConfigError> {
+        self.pop_size = nonzero("population size", popSize)?;
+        Ok(())
+    }
+
+    pub fn set_genome_length(&mut self, genomeLength: usize) -> Result<(), ConfigError> {
+        self.genome_length = nonzero("genome length", genomeLength)?;
+        Ok(())
+    }
+
+    pub fn set_inner_nodes(&mut self, innerNodes: usize) -> Result<(), ConfigError> {
+        if innerNodes > MAX_INNER_NODE_COUNT {
+            return Err(ConfigError::OutOfRange {
+                name: "inner node count",
+                value: innerNodes.to_string(),
+                range: format!("at most {}", MAX_INNER_NODE_COUNT),
+            });
+        }
+
+        self.inner_nodes = innerNodes;
+        Ok(())
+    }
+
+    pub fn set_grid_width(&mut self, gridWidth: usize) -> Result<(), ConfigError> {
+        let previousWidth = self.grid_width;
+        self.grid_width = nonzero("width", gridWidth)?;
+        self.follow_width(previousWidth);
+        Ok(())
+    }
+
+    //The original selection and culls are placed with integer division of the width, so they follow
+    //the width for as long as they haven't been replaced
+    fn follow_width(&mut self, previousWidth: GridValueT) {
+        if self.selection.to_string() == selection::default_criterion(previousWidth).to_string() {
+            self.selection = selection::default_criterion(self.grid_width);
+        }
+        if self.kill_schedule == KillSchedule::original(previousWidth) {
+            self.kill_schedule = KillSchedule::original(self.grid_width);
+        }
+    }
+
+    pub fn set_grid_height(&mut self, gridHeight: usize) -> Result<(), ConfigError> {
+        self.grid_height = nonzero("height", gridHeight)?;
+        Ok(())
+    }
+
+    pub fn set_steps_per_gen(&mut self, stepsPerGen: TimeT) -> Result<(), ConfigError> {
+        self.steps_per_gen = nonzero("steps per generation", stepsPerGen)?;
+        Ok(())
+    }
+
+    //Percent chance per gene
+    pub fn set_mutation_rate(&mut self, mutationRate: MutR) -> Result<(), ConfigError> {
+        if !(0.0..=100.0).contains(&mutationRate) {
+            return Err(ConfigError::OutOfRange {
+                name: "mutation rate",
+                value: mutationRate.to_string(),
+                range: "0 to 100".to_string(),
+            });
+        }
 
         self.mutation_rate = mutationRate;
+        Ok(())
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn set_selection(&mut self, selection: Box<dyn SelectionCriterion>) {
+        self.selection = selection;
+    }
+
+    pub fn set_kill_schedule(&mut self, kill_schedule: KillSchedule) {
+        self.kill_schedule = kill_schedule;
+    }
+
+    pub fn set_kill_enabled(&mut self, killEnabled: bool) {
+        self.kill_enabled = killEnabled;
+    }
+
+    pub fn set_density_radius(&mut self, densityRadius: f32) -> Result<(), ConfigError> {
+        if !densityRadius.is_finite() || densityRadius < 0.0 {
+            return Err(ConfigError::OutOfRange {
+                name: "density radius",
+                value: densityRadius.to_string(),
+                range: "0 or more".to_string(),
+            });
+        }
+
+        self.density_radius = densityRadius;
+        Ok(())
+    }
+
+    pub fn set_sight_distance(&mut self, sightDistance: GridValueT) -> Result<(), ConfigError> {
+        self.sight_distance = nonzero("sight distance", sightDistance)?;
+        Ok(())
+    }
+
+    pub fn set_recurrent(&mut self, recurrent: bool) {
+        self.recurrent = recurrent;
+    }
+
+    pub fn set_inner_decay(&mut self, innerDecay: f32) -> Result<(), ConfigError> {
+        if !(0.0..=1.0).contains(&innerDecay) {
+            return Err(ConfigError::OutOfRange {
+                name: "inner decay",
+                value: innerDecay.to_string(),
+                range: "between 0 and 1".to_string(),
+            });
+        }
+
+        self.inner_decay = innerDecay;
+        Ok(())
+    }
+
+    pub fn set_activations(&mut self, inner: Activation, output: Activation) {
+        self.inner_activation = inner;
+        self.output_activation = output;
+    }
+
+    pub fn set_inherit_activation(&mut self, inheritActivation: bool) {
+        self.inherit_activation = inheritActivation;
+    }
+
+    pub fn set_barriers(&mut self, barriers: BarrierLayout) {
+        self.barriers = barriers;
+    }
+
+    pub fn set_reproduction(&mut self, reproduction: Reproduction) {
+        self.reproduction = reproduction;
+    }
+
+    pub fn set_pairing(&mut self, pairing: Pairing) {
+        self.pairing = pairing;
+    }
+
+    //0 disables periodic saving
+    pub fn set_save_interval(&mut self, saveInterval: usize) {
+        self.save_interval = saveInterval;
+    }
+
+    //0 only writes frames at the end of each generation
+    pub fn set_frame_interval(&mut self, frameInterval: TimeT) {
+        self.frame_interval = frameInterval;
+    }
+
+    pub fn set_frame_format(&mut self, frameFormat: ImageFormat) {
+        self.frame_format = frameFormat;
+    }
+
+    pub fn set_record_gens(&mut self, mut recordGens: Vec<TimeT>) {
+        recordGens.sort_unstable();
+        recordGens.dedup();
+        self.record_gens = recordGens;
     }
 
-    pub fn serialize<T: Write>(&self, writer: &mut T) {
-        writer
-            .write(&(self.pop_size as u64).to_le_bytes())
-            .expect("Error: Failed to write config");
-        writer
-            .write(&(self.genome_length as u64).to_le_bytes())
-            .expect("Error: Failed to write config");
-        writer
-            .write(&(self.grid_width as u64).to_le_bytes())
-            .expect("Error: Failed to write config");
-        writer
-            .write(&(self.grid_height as u64).to_le_bytes())
-            .expect("Error: Failed to write config");
-        writer
-            .write(&(self.mutation_rate).to_le_bytes())
-            .expect("Error: Failed to write config");
-        writer
-            .write(&(self.steps_per_gen as u64).to_le_bytes())
-            .expect("Error: Failed to write config");
-    }
-
-    pub fn deserialize<T: Read>(reader: &mut T) -> Self {
-        let mut buf8 = [0; size_of::<usize>()];
-        let mut buf4 = [0; size_of::<f32>()];
-        reader
-            .read_exact(&mut buf8)
-            .expect("Error: Failed to read config");
-        let pop_size = usize::from_le_bytes(buf8);
-        reader
-            .read_exact(&mut buf8)
-            .expect("Error: Failed to read config");
-        let genome_length = usize::from_le_bytes(buf8);
-        reader
-            .read_exact(&mut buf8)
-            .expect("Error: Failed to read config");
-        let grid_width = usize::from_le_bytes(buf8);
-        reader
-            .read_exact(&mut buf8)
-            .expect("Error: Failed to read config");
-        let grid_height = usize::from_le_bytes(buf8);
-        reader
-            .read_exact(&mut buf4)
-            .expect("Error: Failed to read config");
+    //Takes the options that aren't stored in a snapshot from the command line config
+    //A config file may repeat the snapshot's settings, as the run config of a resumed run does,
+    //but not change them
+    pub fn inherit_run_options(&mut self, other: Config) -> Result<(), ConfigError> {
+        for key in &other.file_snapshot_keys {
+            let value = self.snapshot_value(key);
+            if other.snapshot_value(key) != value {
+                return Err(ConfigError::InvalidValue {
+                    option: key.to_string(),
+                    message: format!(
+                        "A resumed run uses the value saved in the snapshot, {}",
+                        value
+                    ),
+                });
+            }
+        }
+
+        let otherWidth = other.grid_width;
+        self.selection = other.selection;
+        self.kill_schedule = other.kill_schedule;
+        self.kill_enabled = other.kill_enabled;
+        self.density_radius = other.density_radius;
+        self.sight_distance = other.sight_distance;
+        self.recurrent = other.recurrent;
+        self.inner_decay = other.inner_decay;
+        self.inner_activation = other.inner_activation;
+        self.output_activation = other.output_activation;
+        self.inherit_activation = other.inherit_activation;
+        self.barriers = other.barriers;
+        self.reproduction = other.reproduction;
+        self.pairing = other.pairing;
+        self.is_windowing = other.is_windowing;
+        self.save_interval = other.save_interval;
+        self.load_path = other.load_path;
+        self.stats_path = other.stats_path;
+        self.frames_path = other.frames_path;
+        self.frame_interval = other.frame_interval;
+        self.frame_format = other.frame_format;
+        self.record_gens = other.record_gens;
+        self.record_dir = other.record_dir;
+        self.dot_path = other.dot_path;
+        self.dot_cell = other.dot_cell;
+        self.follow_width(otherWidth);
+
+        Ok(())
+    }
+
+    pub fn load_file(path: &str) -> Result<Config, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+        Config::parse_file(&contents)
+    }
+
+    //`key = value` per line, blank lines and lines starting with # are ignored
+    //Keys that are left out keep their default values
+    pub fn parse_file(input: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (line_number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            config
+                .set_from_file(line)
+                .map_err(|err| format!("Line {}: {}", line_number + 1, err))?;
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn set_from_file(&mut self, line: &str) -> Result<(), String> {
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Expected `key = value`, found `{}`", line))?;
+        let (key, value) = (key.trim(), FileValue::parse(value.trim())?);
+
+        match key {
+            "population_size" => self.set_pop_size(value.number()?)?,
+            "genome_length" => self.set_genome_length(value.number()?)?,
+            "inner_nodes" => self.set_inner_nodes(value.number()?)?,
+            "width" => self.set_grid_width(value.number()?)?,
+            "height" => self.set_grid_height(value.number()?)?,
+            "mutation_rate" => self.set_mutation_rate(value.number()?)?,
+            "steps_per_gen" => self.set_steps_per_gen(value.number()?)?,
+            "seed" => self.seed = value.number()?,
+            "selection" => self.selection = selection::parse_criterion(value.string()?)?,
+            //Either the events themselves, or the path of a schedule file
+            "kill_schedule" => {
+                self.kill_schedule = match value {
+                    FileValue::Array(events) => KillSchedule::parse(&events.join("\n"))?,
+                    value => KillSchedule::load(value.string()?)?,
+                }
+            }
+            "kill_forward" => self.kill_enabled = value.number()?,
+            "density_radius" => self.set_density_radius(value.number()?)?,
+            "sight_distance" => self.set_sight_distance(value.number()?)?,
+            "recurrent" => self.recurrent = value.number()?,
+            "inner_decay" => self.set_inner_decay(value.number()?)?,
+            "inner_activation" => {
+                self.inner_activation = Activation::from_name(value.string()?)
+                    .ok_or_else(|| format!("Unknown activation `{}`", value))?
+            }
+            "output_activation" => {
+                self.output_activation = Activation::from_name(value.string()?)
+                    .ok_or_else(|| format!("Unknown activation `{}`", value))?
+            }
+            "inherit_activation" => self.inherit_activation = value.number()?,
+            "barriers" => {
+                self.barriers = BarrierLayout::from_name(value.string()?)
+                    .ok_or_else(|| format!("Unknown barrier layout `{}`", value))?
+            }
+            "reproduction" => {
+                self.reproduction = Reproduction::from_name(value.string()?)
+                    .ok_or_else(|| format!("Unknown reproduction mode `{}`", value))?
+            }
+            "pairing" => {
+                self.pairing = Pairing::from_name(value.string()?)
+                    .ok_or_else(|| format!("Unknown pairing `{}`", value))?
+            }
+            "windowing" => self.is_windowing = value.number()?,
+            "save_interval" => self.save_interval = value.number()?,
+            "load" => self.load_path = Some(value.string()?.to_string()),
+            "stats" => self.stats_path = Some(value.string()?.to_string()),
+            "frames" => self.frames_path = Some(value.string()?.to_string()),
+            "frame_interval" => self.frame_interval = value.number()?,
+            "record_gens" => self.set_record_gens(parse_generations(value.string()?)?),
+            "record_dir" => self.record_dir = value.string()?.to_string(),
+            "frame_format" => {
+                self.frame_format = ImageFormat::from_name(value.string()?)
+                    .ok_or_else(|| format!("Unknown image format `{}`", value))?
+            }
+            key => return Err(format!("Unknown key `{}`", key)),
+        }
+
+        if let Some(key) = SNAPSHOT_KEYS
+            .iter()
+            .find(|snapshotKey| **snapshotKey == key)
+        {
+            self.file_snapshot_keys.push(key);
+        }
+
+        Ok(())
+    }
+
+    //The value of one of SNAPSHOT_KEYS as it's written to a file
+    fn snapshot_value(&self, key: &str) -> String {
+        match key {
+            "population_size" => self.pop_size.to_string(),
+            "genome_length" => self.genome_length.to_string(),
+            "inner_nodes" => self.inner_nodes.to_string(),
+            "width" => self.grid_width.to_string(),
+            "height" => self.grid_height.to_string(),
+            "mutation_rate" => format!("{:?}", self.mutation_rate),
+            "steps_per_gen" => self.steps_per_gen.to_string(),
+            "seed" => self.seed.to_string(),
+            key => unreachable!("`{}` is not stored in snapshots", key),
+        }
+    }
+
+    //Every setting in the format read by parse_file
+    pub fn to_file_string(&self) -> String {
+        let mut output = String::new();
+
+        let _ = writeln!(output, "population_size = {}", self.pop_size);
+        let _ = writeln!(output, "genome_length = {}", self.genome_length);
+        let _ = writeln!(output, "inner_nodes = {}", self.inner_nodes);
+        let _ = writeln!(output, "width = {}", self.grid_width);
+        let _ = writeln!(output, "height = {}", self.grid_height);
+        let _ = writeln!(output, "mutation_rate = {:?}", self.mutation_rate);
+        let _ = writeln!(output, "steps_per_gen = {}", self.steps_per_gen);
+        let _ = writeln!(output, "seed = {}", self.seed);
+        let _ = writeln!(
+            output,
+            "selection = {}",
+            FileValue::Str(self.selection.to_string())
+        );
+        let events = self
+            .kill_schedule
+            .get_events()
+            .iter()
+            .map(|event| event.to_string())
+            .collect();
+        let _ = writeln!(output, "kill_schedule = {}", FileValue::Array(events));
+        let _ = writeln!(output, "kill_forward = {}", self.kill_enabled);
+        let _ = writeln!(output, "density_radius = {:?}", self.density_radius);
+        let _ = writeln!(output, "sight_distance = {}", self.sight_distance);
+        let _ = writeln!(output, "recurrent = {}", self.recurrent);
+        let _ = writeln!(output, "inner_decay = {:?}", self.inner_decay);
+        let _ = writeln!(
+            output,
+            "inner_activation = {}",
+            FileValue::Str(self.inner_activation.get_name().to_string())
+        );
+        let _ = writeln!(
+            output,
+            "output_activation = {}",
+            FileValue::Str(self.output_activation.get_name().to_string())
+        );
+        let _ = writeln!(output, "inherit_activation = {}", self.inherit_activation);
+        let _ = writeln!(
+            output,
+            "barriers = {}",
+            FileValue::Str(self.barriers.get_name().to_string())
+        );
+        let _ = writeln!(
+            output,
+            "reproduction = {}",
+            FileValue::Str(self.reproduction.get_name().to_string())
+        );
+        let _ = writeln!(
+            output,
+            "pairing = {}",
+            FileValue::Str(self.pairing.get_name().to_string())
+        );
+        let _ = writeln!(output, "windowing = {}", self.is_windowing);
+        let _ = writeln!(output, "save_interval = {}", self.save_interval);
+        if let Some(path) = &self.load_path {
+            let _ = writeln!(output, "load = {}", FileValue::Str(path.clone()));
+        }
+        if let Some(path) = &self.stats_path {
+            let _ = writeln!(output, "stats = {}", FileValue::Str(path.clone()));
+        }
+        if let Some(path) = &self.frames_path {
+            let _ = writeln!(output, "frames = {}", FileValue::Str(path.clone()));
+        }
+        let _ = writeln!(output, "frame_interval = {}", self.frame_interval);
+        let _ = writeln!(
+            output,
+            "frame_format = {}",
+            FileValue::Str(self.frame_format.get_name().to_string())
+        );
+        let _ = writeln!(
+            output,
+            "record_gens = {}",
+            FileValue::Str(generations_to_string(&self.record_gens))
+        );
+        let _ = writeln!(
+            output,
+            "record_dir = {}",
+            FileValue::Str(self.record_dir.clone())
+        );
+
+        output
+    }
+
+    pub fn write_file(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, self.to_file_string())
+    }
+
+    pub fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_all(&(self.pop_size as u64).to_le_bytes())?;
+        writer.write_all(&(self.genome_length as u64).to_le_bytes())?;
+        writer.write_all(&(self.grid_width as u64).to_le_bytes())?;
+        writer.write_all(&(self.grid_height as u64).to_le_bytes())?;
+        writer.write_all(&(self.mutation_rate).to_le_bytes())?;
+        writer.write_all(&(self.steps_per_gen as u64).to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&(self.inner_nodes as u64).to_le_bytes())
+    }
+
+    pub fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self> {
+        let mut buf8 = [0; size_of::<u64>()];
+        let mut buf4 = [0; size_of::<MutR>()];
+        reader.read_exact(&mut buf8)?;
+        let pop_size = u64::from_le_bytes(buf8) as usize;
+        reader.read_exact(&mut buf8)?;
+        let genome_length = u64::from_le_bytes(buf8) as usize;
+        reader.read_exact(&mut buf8)?;
+        let grid_width = u64::from_le_bytes(buf8) as GridValueT;
+        reader.read_exact(&mut buf8)?;
+        let grid_height = u64::from_le_bytes(buf8) as GridValueT;
+        reader.read_exact(&mut buf4)?;
         let mutation_rate = MutR::from_le_bytes(buf4);
-        reader
-            .read_exact(&mut buf8)
-            .expect("Error: Failed to read config");
-        let steps_per_gen = usize::from_le_bytes(buf8);
-        Config {
+        reader.read_exact(&mut buf8)?;
+        let steps_per_gen = u64::from_le_bytes(buf8) as TimeT;
+        reader.read_exact(&mut buf8)?;
+        let seed = u64::from_le_bytes(buf8);
+        reader.read_exact(&mut buf8)?;
+        let inner_nodes = u64::from_le_bytes(buf8) as usize;
+
+        if pop_size == 0
+            || genome_length == 0
+            || inner_nodes > MAX_INNER_NODE_COUNT
+            || steps_per_gen == 0
+            || grid_width
+                .checked_mul(grid_height)
+                .is_none_or(|tiles| pop_size > tiles)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid config values",
+            ));
+        }
+
+        Ok(Config {
             pop_size,
             genome_length,
+            inner_nodes,
             grid_width,
             grid_height,
             mutation_rate,
             steps_per_gen,
+            seed,
+            selection: selection::default_criterion(grid_width),
+            kill_schedule: KillSchedule::original(grid_width),
+            kill_enabled: false,
+            density_radius: 2.5,
+            sight_distance: 8,
+            recurrent: false,
+            inner_decay: 0.5,
+            inner_activation: Activation::Relu,
+            output_activation: Activation::Identity,
+            inherit_activation: false,
+            barriers: BarrierLayout::Empty,
+            reproduction: Reproduction::Asexual,
+            pairing: Pairing::Random,
             is_windowing: false,
-        }
+            save_interval: 0,
+            load_path: None,
+            stats_path: None,
+            frames_path: None,
+            frame_interval: 0,
+            frame_format: ImageFormat::Png,
+            record_gens: Vec::new(),
+            record_dir: DEFAULT_RECORD_DIR.to_string(),
+            dot_path: None,
+            dot_cell: 0,
+            file_snapshot_keys: Vec::new(),
+        })
     }
 }
 
@@ -251,8 +1112,58 @@ impl Display for Config {
             self.grid_width, self.grid_height
         )?;
         writeln!(f, "Genome Length: {}", self.genome_length)?;
+        writeln!(f, "Inner Nodes: {}", self.inner_nodes)?;
         writeln!(f, "Mutation Rate: {}%", self.mutation_rate)?;
-        writeln!(f, "Windowing: {}", self.is_windowing)
+        writeln!(f, "Seed: {}", self.seed)?;
+        writeln!(f, "Selection: {}", self.selection)?;
+        write!(f, "Kill Schedule:\n{}", self.kill_schedule)?;
+        writeln!(f, "Kill Forward: {}", self.kill_enabled)?;
+        writeln!(f, "Density Radius: {}", self.density_radius)?;
+        writeln!(f, "Sight Distance: {}", self.sight_distance)?;
+        match self.get_inner_memory() {
+            Some(decay) => writeln!(f, "Recurrent Inner Nodes: decay {}", decay)?,
+            None => writeln!(f, "Recurrent Inner Nodes: off")?,
+        }
+        if self.inherit_activation {
+            writeln!(f, "Activations: inherited")?;
+        } else {
+            writeln!(
+                f,
+                "Activations: {} inner, {} output",
+                self.inner_activation.get_name(),
+                self.output_activation.get_name()
+            )?;
+        }
+        writeln!(f, "Barriers: {}", self.barriers.get_name())?;
+        write!(f, "Reproduction: {}", self.reproduction.get_name())?;
+        if self.reproduction == Reproduction::Sexual {
+            write!(f, " ({} pairing)", self.pairing.get_name())?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Windowing: {}", self.is_windowing)?;
+        writeln!(f, "Save Interval: {}", self.save_interval)?;
+        if let Some(path) = &self.load_path {
+            writeln!(f, "Loaded From: {}", path)?;
+        }
+        if let Some(path) = &self.stats_path {
+            writeln!(f, "Stats: {}", path)?;
+        }
+        if let Some(path) = &self.frames_path {
+            write!(f, "Frames: {} ({}", path, self.frame_format.get_name())?;
+            if self.frame_interval != 0 {
+                write!(f, ", every {} steps", self.frame_interval)?;
+            }
+            writeln!(f, ")")?;
+        }
+        if !self.record_gens.is_empty() {
+            writeln!(
+                f,
+                "Recording: generations {} to {}",
+                generations_to_string(&self.record_gens),
+                self.record_dir
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -261,11 +1172,482 @@ impl Default for Config {
         Self {
             pop_size: 4000,
             genome_length: 20,
+            inner_nodes: DEFAULT_INNER_NODE_COUNT,
             grid_width: 200,
             grid_height: 200,
             mutation_rate: 0.1,
             steps_per_gen: 250,
+            seed: thread_rng().gen(),
+            selection: selection::default_criterion(200),
+            kill_schedule: KillSchedule::original(200),
+            kill_enabled: false,
+            density_radius: 2.5,
+            sight_distance: 8,
+            recurrent: false,
+            inner_decay: 0.5,
+            inner_activation: Activation::Relu,
+            output_activation: Activation::Identity,
+            inherit_activation: false,
+            barriers: BarrierLayout::Empty,
+            reproduction: Reproduction::Asexual,
+            pairing: Pairing::Random,
             is_windowing: false,
+            save_interval: 0,
+            load_path: None,
+            stats_path: None,
+            frames_path: None,
+            frame_interval: 0,
+            frame_format: ImageFormat::Png,
+            record_gens: Vec::new(),
+            record_dir: DEFAULT_RECORD_DIR.to_string(),
+            dot_path: None,
+            dot_cell: 0,
+            file_snapshot_keys: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidNumber {
+        option: String,
+        value: String,
+    },
+    InvalidValue {
+        option: String,
+        message: String,
+    },
+    InvalidFile {
+        path: String,
+        message: String,
+    },
+    Zero(&'static str),
+    OutOfRange {
+        name: &'static str,
+        value: String,
+        range: String,
+    },
+    TooDense {
+        pop_size: usize,
+        tiles: usize,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::UnknownOption(option) => write!(f, "Unknown option `{}`", option),
+            ConfigError::MissingValue(option) => write!(f, "Missing value for `{}`", option),
+            ConfigError::InvalidNumber { option, value } => {
+                write!(f, "Invalid number `{}` for `{}`", value, option)
+            }
+            ConfigError::InvalidValue { option, message } => {
+                write!(f, "Invalid value for `{}`: {}", option, message)
+            }
+            ConfigError::InvalidFile { path, message } => {
+                write!(f, "Invalid file {}: {}", path, message)
+            }
+            ConfigError::Zero(name) => write!(f, "The {} must not be 0", name),
+            ConfigError::OutOfRange { name, value, range } => {
+                write!(f, "The {} must be {}, found {}", name, range, value)
+            }
+            ConfigError::TooDense { pop_size, tiles } => write!(
+                f,
+                "A population of {} does not fit on a grid with {} tiles",
+                pop_size, tiles
+            ),
+        }
+    }
+}
+
+impl From<ConfigError> for String {
+    fn from(err: ConfigError) -> Self {
+        err.to_string()
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, ConfigError> {
+    value.parse::<T>().map_err(|_| ConfigError::InvalidNumber {
+        option: option.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn parse_activation(option: String, value: &str) -> Result<Activation, ConfigError> {
+    Activation::from_name(value).ok_or_else(|| ConfigError::InvalidValue {
+        option,
+        message: format!(
+            "Unknown activation `{}`, expected relu, tanh, sigmoid, identity or step",
+            value
+        ),
+    })
+}
+
+//Comma separated generations, eg. `0,100,500`
+fn parse_generations(input: &str) -> Result<Vec<TimeT>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|generation| !generation.is_empty())
+        .map(|generation| {
+            generation
+                .parse::<TimeT>()
+                .map_err(|_| format!("Invalid generation `{}`", generation))
+        })
+        .collect()
+}
+
+fn generations_to_string(generations: &[TimeT]) -> String {
+    generations
+        .iter()
+        .map(|generation| generation.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn nonzero(name: &'static str, value: usize) -> Result<usize, ConfigError> {
+    if value == 0 {
+        return Err(ConfigError::Zero(name));
+    }
+    Ok(value)
+}
+
+//Values in a config file, a subset of TOML
+#[derive(Clone, Debug, PartialEq)]
+enum FileValue {
+    //Numbers and booleans
+    Bare(String),
+    Str(String),
+    Array(Vec<String>),
+}
+
+impl FileValue {
+    fn parse(input: &str) -> Result<FileValue, String> {
+        if let Some(inner) = input.strip_prefix('[') {
+            let inner = inner
+                .strip_suffix(']')
+                .ok_or_else(|| format!("Missing ']' in `{}`", input))?;
+
+            let mut strings = Vec::new();
+            let mut rest = inner.trim();
+            while !rest.is_empty() {
+                let (string, remaining) = parse_string(rest)?;
+                strings.push(string);
+
+                rest = remaining.trim_start();
+                rest = match rest.strip_prefix(',') {
+                    Some(remaining) => remaining.trim_start(),
+                    None if rest.is_empty() => rest,
+                    None => return Err(format!("Expected ',' in `{}`", input)),
+                };
+            }
+
+            Ok(FileValue::Array(strings))
+        } else if input.starts_with('"') {
+            match parse_string(input)? {
+                (string, "") => Ok(FileValue::Str(string)),
+                (_, rest) => Err(format!("Unexpected `{}` after string", rest.trim())),
+            }
+        } else {
+            Ok(FileValue::Bare(input.to_string()))
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&self) -> Result<T, String> {
+        match self {
+            FileValue::Bare(value) => value
+                .parse::<T>()
+                .map_err(|_| format!("Invalid value `{}`", value)),
+            value => Err(format!("Expected a number or boolean, found {}", value)),
+        }
+    }
+
+    fn string(&self) -> Result<&str, String> {
+        match self {
+            FileValue::Str(value) => Ok(value),
+            value => Err(format!("Expected a quoted string, found {}", value)),
+        }
+    }
+}
+
+//Parses a leading quoted string, returning it and the remaining input
+fn parse_string(input: &str) -> Result<(String, &str), String> {
+    let mut chars = input
+        .strip_prefix('"')
+        .ok_or_else(|| format!("Expected a quoted string, found `{}`", input))?
+        .char_indices();
+
+    let mut string = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &input[index + 2..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => string.push('"'),
+                Some((_, '\\')) => string.push('\\'),
+                _ => return Err(format!("Invalid escape in `{}`", input)),
+            },
+            c => string.push(c),
+        }
+    }
+
+    Err(format!("Missing closing quote in `{}`", input))
+}
+
+impl Display for FileValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quote =
+            |string: &str| format!("\"{}\"", string.replace('\\', "\\\\").replace('"', "\\\""));
+
+        match self {
+            FileValue::Bare(value) => write!(f, "{}", value),
+            FileValue::Str(value) => write!(f, "{}", quote(value)),
+            FileValue::Array(values) => {
+                let values: Vec<String> = values.iter().map(|value| quote(value)).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::{selection, Activation, Config, ConfigError, ImageFormat, KillSchedule};
+
+    #[test]
+    fn file_round_trip() {
+        let config = Config::parse_file(
+            "# comment\n\npopulation_size = 100\nwidth = 20\nheight = 20\nseed = 42\n\
+             selection = \"corners(0.2)\"\nkill_schedule = [\"10 circle(0.5, 0.5, 0.25) 0.5\"]\n\
+             kill_forward = true\nbarriers = \"maze\"\nframe_format = \"ppm\"\nload = \"saves\\\\gen \\\"1\\\".sav\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.get_pop_size(), 100);
+        assert_eq!(config.get_seed(), 42);
+        assert_eq!(config.get_selection().to_string(), "corners(0.2)");
+        assert_eq!(config.get_kill_schedule().get_events().len(), 1);
+        assert!(config.get_kill_enabled());
+        assert_eq!(config.get_frame_format(), ImageFormat::Ppm);
+        assert_eq!(config.get_load_path(), Some("saves\\gen \"1\".sav"));
+        assert_eq!(
+            config.get_genome_size(),
+            Config::default().get_genome_size()
+        );
+
+        let reparsed = Config::parse_file(&config.to_file_string()).unwrap();
+        assert_eq!(reparsed.to_file_string(), config.to_file_string());
+        assert_eq!(reparsed.to_string(), config.to_string());
+    }
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn cli_errors() {
+        let config =
+            Config::from_args(args("-p 10 --width 5 --height 4 --mutation-rate 2.5")).unwrap();
+        assert_eq!(config.get_pop_size(), 10);
+        assert_eq!(config.get_mutation_rate(), 2.5);
+
+        let config = Config::from_args(args("--record-gens 500,0,100,100")).unwrap();
+        assert!(config.is_recorded(0) && config.is_recorded(100) && !config.is_recorded(1));
+        assert_eq!(
+            Config::from_args(args("--record-gens 1,x")).err(),
+            Some(ConfigError::InvalidValue {
+                option: "--record-gens".to_string(),
+                message: "Invalid generation `x`".to_string()
+            })
+        );
+
+        assert_eq!(
+            Config::from_args(args("--colour red")).err(),
+            Some(ConfigError::UnknownOption("--colour".to_string()))
+        );
+        assert_eq!(
+            Config::from_args(args("-p ten")).err(),
+            Some(ConfigError::InvalidNumber {
+                option: "-p".to_string(),
+                value: "ten".to_string()
+            })
+        );
+        assert_eq!(
+            Config::from_args(args("--width 0")).err(),
+            Some(ConfigError::Zero("width"))
+        );
+        assert_eq!(
+            Config::from_args(args("--seed")).err(),
+            Some(ConfigError::MissingValue("--seed".to_string()))
+        );
+        assert_eq!(
+            Config::from_args(args("-p 50 --width 5 --height 5")).err(),
+            Some(ConfigError::TooDense {
+                pop_size: 50,
+                tiles: 25
+            })
+        );
+        assert!(matches!(
+            Config::from_args(args("-m 150")),
+            Err(ConfigError::OutOfRange { .. })
+        ));
+        assert_eq!(
+            Config::from_args(args("--inner-nodes 0"))
+                .unwrap()
+                .get_inner_nodes(),
+            0
+        );
+        assert_eq!(
+            Config::from_args(args("--recurrent --inner-decay 0.25"))
+                .unwrap()
+                .get_inner_memory(),
+            Some(0.25)
+        );
+        assert!(matches!(
+            Config::from_args(args("--inner-decay 1.5")),
+            Err(ConfigError::OutOfRange { .. })
+        ));
+        let config =
+            Config::from_args(args("--inner-activation tanh --output-activation step")).unwrap();
+        assert_eq!(
+            (
+                config.get_inner_activation(),
+                config.get_output_activation()
+            ),
+            (Activation::Tanh, Activation::Step)
+        );
+        assert!(matches!(
+            Config::from_args(args("--output-activation softmax")),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            Config::from_args(args("--inner-nodes 65")),
+            Err(ConfigError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            Config::from_args(args("--barriers walls")),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn file_errors() {
+        assert!(Config::parse_file("population_size = 0").is_err());
+        assert!(Config::parse_file("population_size = 500\nwidth = 10\nheight = 10").is_err());
+        assert!(Config::parse_file("colour = 1").is_err());
+        assert!(Config::parse_file("selection = corners(0.2)").is_err());
+        assert!(Config::parse_file("barriers = \"walls\"").is_err());
+        assert!(Config::parse_file("kill_schedule = [\"10 circle(0, 0, 1)\"").is_err());
+    }
+
+    #[test]
+    fn new_checks_values() {
+        assert_eq!(
+            Config::new(65, 8, 8, 8, 0.1, 30, false).err(),
+            Some(ConfigError::TooDense {
+                pop_size: 65,
+                tiles: 64
+            })
+        );
+        assert_eq!(
+            Config::new(10, 8, 8, 8, 0.1, 0, false).err(),
+            Some(ConfigError::Zero("steps per generation"))
+        );
+
+        let config = Config::new(64, 8, 8, 8, 0.1, 30, true).unwrap();
+        assert!(config.get_is_windowing());
+        assert_eq!(config.get_grid_width(), 8);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut config = Config::new(50, 8, 16, 12, 0.1, 30, false).unwrap();
+        config.set_seed(11);
+
+        let mut buffer = Vec::new();
+        config.serialize(&mut buffer).unwrap();
+        let loaded = Config::deserialize(&mut buffer.as_slice()).unwrap();
+        assert_eq!(
+            (
+                loaded.get_pop_size(),
+                loaded.get_grid_width(),
+                loaded.get_grid_height(),
+                loaded.get_seed()
+            ),
+            (50, 16, 12, 11)
+        );
+
+        //A width and height that overflow when multiplied
+        buffer[16..32].copy_from_slice(&[0xFF; 16]);
+        assert_eq!(
+            Config::deserialize(&mut buffer.as_slice())
+                .err()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn defaults_follow_width() {
+        let original = |width| selection::default_criterion(width).to_string();
+
+        let config = Config::from_args(args("--width 13 -p 10")).unwrap();
+        assert_eq!(config.get_selection().to_string(), original(13));
+
+        let config = Config::from_args(args("--selection corners(0.1) --width 13 -p 10")).unwrap();
+        assert_eq!(config.get_selection().to_string(), "corners(0.1)");
+
+        //A resumed run places the original rule on the saved grid
+        let mut loaded = Config::new(10, 4, 10, 10, 0.1, 30, false).unwrap();
+        loaded
+            .inherit_run_options(Config::from_args(args("--width 13 -p 10")).unwrap())
+            .unwrap();
+        assert_eq!(loaded.get_selection().to_string(), original(10));
+        assert_eq!(loaded.get_kill_schedule(), &KillSchedule::original(10));
+
+        let config = Config::from_args(args("--width 13 -p 10")).unwrap();
+        assert_eq!(config.get_kill_schedule(), &KillSchedule::original(13));
+    }
+
+    #[test]
+    fn resume_rejects_snapshot_options() {
+        for (option, value) in [
+            ("-p", "500"),
+            ("--genome-length", "8"),
+            ("--inner-nodes", "4"),
+            ("--width", "300"),
+            ("--height", "300"),
+            ("-m", "0.5"),
+            ("--steps-per-gen", "100"),
+            ("--seed", "4"),
+        ] {
+            assert_eq!(
+                Config::from_args(args(&format!("file=gen.sav {} {}", option, value))).err(),
+                Some(ConfigError::InvalidValue {
+                    option: option.to_string(),
+                    message: "A resumed run uses the value saved in the snapshot".to_string(),
+                })
+            );
+            assert!(Config::from_args(args(&format!("{} {}", option, value))).is_ok());
+        }
+
+        //A config file can repeat the snapshot's values but not change them
+        let mut loaded = Config::new(500, 8, 100, 100, 0.1, 30, false).unwrap();
+        loaded.set_seed(4);
+        let file = Config::parse_file(&loaded.to_file_string()).unwrap();
+        assert!(loaded.inherit_run_options(file).is_ok());
+
+        let file = Config::parse_file("load = \"gen.sav\"\npopulation_size = 400").unwrap();
+        assert_eq!(
+            loaded.inherit_run_options(file).err(),
+            Some(ConfigError::InvalidValue {
+                option: "population_size".to_string(),
+                message: "A resumed run uses the value saved in the snapshot, 500".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::{self, Write};
+
+use crate::{
+    gene::{Gene, NodeID},
+    neuron::NeuralNet,
+};
+
+const POSITIVE_COLOUR: &str = "#2166ac";
+const NEGATIVE_COLOUR: &str = "#b2182b";
+const UNUSED_COLOUR: &str = "gray";
+
+const MIN_PEN_WIDTH: f32 = 0.5;
+const MAX_PEN_WIDTH: f32 = 4.0;
+
+//Connections with a nonzero net weight, in feed forward order
+fn get_edges(net: &NeuralNet) -> Vec<(NodeID, NodeID, f32)> {
+    let nodes = || NodeID::get_nodes(net.get_inner_nodes());
+
+    let mut edges = Vec::new();
+    for head in nodes().filter(|node| !node.is_output()) {
+        for tail in nodes().filter(|node| !node.is_input()) {
+            let weight = net.get_weight(head, tail);
+            if weight != 0.0 {
+                edges.push((head, tail, weight));
+            }
+        }
+    }
+
+    edges
+}
+
+//Inner nodes with a path to an output, anything else can't change what the cell does
+fn get_reaching_inner(edges: &[(NodeID, NodeID, f32)], inner_nodes: usize) -> Vec<bool> {
+    let mut reaching = vec![false; inner_nodes];
+
+    loop {
+        let mut changed = false;
+
+        for (head, tail, _) in edges {
+            if head.is_inner()
+                && !reaching[head.get_inner_index()]
+                && (tail.is_output() || (tail.is_inner() && reaching[tail.get_inner_index()]))
+            {
+                reaching[head.get_inner_index()] = true;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return reaching;
+        }
+    }
+}
+
+pub fn write_dot<W: Write>(
+    writer: &mut W,
+    name: &str,
+    genome: &[Gene],
+    inner_nodes: usize,
+) -> io::Result<()> {
+    let net = NeuralNet::new(genome, inner_nodes);
+    let edges = get_edges(&net);
+    let reaching = get_reaching_inner(&edges, inner_nodes);
+    let is_unused = |node: NodeID| node.is_inner() && !reaching[node.get_inner_index()];
+    let get_bias = |node: NodeID| {
+        if node.is_input() {
+            0.0
+        } else {
+            net.get_bias(node)
+        }
+    };
+
+    //A bias alone is enough to show a node, an output with one acts even without connections
+    let mut used: Vec<bool> = NodeID::get_nodes(inner_nodes)
+        .map(|node| get_bias(node) != 0.0)
+        .collect();
+    for (head, tail, _) in &edges {
+        used[head.get_layout_index(inner_nodes)] = true;
+        used[tail.get_layout_index(inner_nodes)] = true;
+    }
+    let used_nodes =
+        || NodeID::get_nodes(inner_nodes).filter(|node| used[node.get_layout_index(inner_nodes)]);
+
+    writeln!(writer, "digraph \"{}\" {{", name.replace('"', "\\\""))?;
+    writeln!(writer, "    rankdir=LR;")?;
+    writeln!(writer, "    node [fontname=\"Helvetica\"];")?;
+
+    for node in used_nodes() {
+        let shape = if node.is_input() {
+            "box"
+        } else if node.is_inner() {
+            "ellipse"
+        } else {
+            "doubleoctagon"
+        };
+        let bias = match get_bias(node) {
+            bias if bias != 0.0 => format!(", xlabel=\"bias {:.2}\"", bias),
+            _ => String::new(),
+        };
+        if is_unused(node) {
+            writeln!(
+                writer,
+                "    \"{:?}\" [shape={}, style=dashed, color={}, fontcolor={}{}];",
+                node, shape, UNUSED_COLOUR, UNUSED_COLOUR, bias
+            )?;
+        } else {
+            writeln!(writer, "    \"{:?}\" [shape={}{}];", node, shape, bias)?;
+        }
+    }
+
+    //Inputs on the left, outputs on the right
+    for (rank, in_rank) in [
+        ("source", NodeID::is_input as fn(&NodeID) -> bool),
+        ("sink", NodeID::is_output),
+    ] {
+        let nodes: Vec<String> = used_nodes()
+            .filter(in_rank)
+            .map(|node| format!(" \"{:?}\";", node))
+            .collect();
+
+        if !nodes.is_empty() {
+            writeln!(writer, "    {{ rank={};{} }}", rank, nodes.concat())?;
+        }
+    }
+
+    let max_weight = edges
+        .iter()
+        .map(|(_, _, weight)| weight.abs())
+        .fold(0.0, f32::max);
+
+    for (head, tail, weight) in &edges {
+        let colour = if is_unused(*head) || is_unused(*tail) {
+            UNUSED_COLOUR
+        } else if *weight > 0.0 {
+            POSITIVE_COLOUR
+        } else {
+            NEGATIVE_COLOUR
+        };
+        let width = MIN_PEN_WIDTH + (MAX_PEN_WIDTH - MIN_PEN_WIDTH) * weight.abs() / max_weight;
+
+        writeln!(
+            writer,
+            "    \"{:?}\" -> \"{:?}\" [color=\"{}\", penwidth={:.2}, label=\"{:.2}\"];",
+            head, tail, colour, width, weight
+        )?;
+    }
+
+    writeln!(writer, "}}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_dot;
+    use crate::gene::{Gene, NodeID};
+
+    #[test]
+    fn greys_out_dead_ends() {
+        let genome = [
+            Gene::from_parts(NodeID::DistX, NodeID::as_inner(0), 4000, 3),
+            Gene::from_parts(NodeID::as_inner(0), NodeID::MoveEast, -8000, 3),
+            Gene::from_parts(NodeID::Age, NodeID::as_inner(1), 2000, 3),
+            Gene::from_parts(NodeID::DistToWallForward, NodeID::KillForward, 1000, 3),
+        ];
+
+        let mut output = Vec::new();
+        write_dot(&mut output, "cell 0", &genome, 3).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("digraph \"cell 0\" {"));
+        assert!(output.contains("\"Inner1\" [shape=ellipse];"));
+        assert!(output.contains("\"Inner2\" [shape=ellipse, style=dashed, color=gray"));
+        assert!(output.contains("\"Age\" -> \"Inner2\" [color=\"gray\""));
+        assert!(output.contains("\"Inner1\" -> \"MoveEast\" [color=\"#b2182b\", penwidth=4.00"));
+        assert!(output.contains("\"DistX\" -> \"Inner1\" [color=\"#2166ac\""));
+        assert!(!output.contains("Inner3"));
+        assert_eq!(output.matches("->").count(), 4);
+    }
+
+    #[test]
+    fn labels_biases() {
+        let genome = [
+            Gene::from_parts(NodeID::Bias, NodeID::MoveWest, 8191, 3),
+            Gene::from_parts(NodeID::Bias, NodeID::as_inner(2), -4095, 3),
+        ];
+
+        let mut output = Vec::new();
+        write_dot(&mut output, "cell 0", &genome, 3).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("\"MoveWest\" [shape=doubleoctagon, xlabel=\"bias 1.00\"];"));
+        assert!(output.contains("\"Inner3\" [shape=ellipse, style=dashed"));
+        assert!(output.contains("xlabel=\"bias -0.50\""));
+        assert!(!output.contains("->"));
+    }
+}
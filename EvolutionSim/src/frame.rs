@@ -0,0 +1,332 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::{
+    grid::Grid,
+    observer::{Context, Observer},
+    population::Population,
+    selection::normalize_coords,
+    simulation::StepReport,
+    Config, TimeT,
+};
+
+//Frames are scaled up so small grids are still readable
+const MIN_FRAME_SIZE: usize = 512;
+
+const BACKGROUND: (u8, u8, u8) = (255, 255, 255);
+const BARRIER: (u8, u8, u8) = (89, 89, 89);
+const SELECTION_TINT: (u8, u8, u8) = (120, 200, 120);
+const KILL_TINT: (u8, u8, u8) = (230, 120, 120);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+//An RGB image of the grid drawn without OpenGL, for runs without a display
+pub struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            pixels: vec![0; width * height * 3],
+        }
+    }
+
+    //Draws the state after step, kill zones triggered on that step are drawn stronger than the rest
+    pub fn render(config: &Config, grid: &Grid, population: &Population, step: TimeT) -> Frame {
+        let dimensions = grid.get_dimensions();
+        let scale = (MIN_FRAME_SIZE / dimensions.0.max(dimensions.1)).max(1);
+
+        let mut frame = Frame::new(dimensions.0 * scale, dimensions.1 * scale);
+        let events = config.get_kill_schedule().get_events();
+
+        for y in 0..dimensions.1 {
+            for x in 0..dimensions.0 {
+                let (nx, ny) = normalize_coords((x, y), dimensions);
+
+                let mut colour = BACKGROUND;
+                if config.get_selection().is_selected(nx, ny) {
+                    colour = blend(colour, SELECTION_TINT, 0.5);
+                }
+                for event in events.iter().filter(|event| event.zone.contains(nx, ny)) {
+                    let strength = if event.is_triggered(step, config.get_steps_per_gen()) {
+                        0.8
+                    } else {
+                        0.3
+                    };
+                    colour = blend(colour, KILL_TINT, strength);
+                }
+                if grid.is_barrier(x, y) {
+                    colour = BARRIER;
+                }
+
+                frame.fill_tile(x, y, scale, colour);
+            }
+        }
+
+        for index in population.getLivingIndices() {
+            let (x, y) = population.getCellMovementData(index).getCoords();
+            frame.fill_tile(x, y, scale, population.getCellMiscData(index).color);
+        }
+
+        frame
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        let index = (x + y * self.width) * 3;
+        (
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+        )
+    }
+
+    //Tile (0, 0) is the south-west corner while row 0 of the image is the top
+    fn fill_tile(&mut self, x: usize, y: usize, scale: usize, colour: (u8, u8, u8)) {
+        let top = self.height - (y + 1) * scale;
+
+        for row in top..top + scale {
+            for column in x * scale..(x + 1) * scale {
+                let index = (column + row * self.width) * 3;
+                self.pixels[index..index + 3].copy_from_slice(&[colour.0, colour.1, colour.2]);
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str, format: ImageFormat) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        match format {
+            ImageFormat::Ppm => self.write_ppm(&mut writer)?,
+            ImageFormat::Png => self.write_png(&mut writer)?,
+        }
+
+        writer.flush()
+    }
+
+    pub fn write_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)
+    }
+
+    //Written with stored deflate blocks, so the files are large but need no compression library
+    pub fn write_png<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'])?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        //8 bit depth, truecolour, deflate, adaptive filtering, no interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(writer, b"IHDR", &header)?;
+
+        //Every scanline starts with filter type 0
+        let mut scanlines = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width * 3) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        write_chunk(writer, b"IDAT", &zlib_stored(&scanlines))?;
+
+        write_chunk(writer, b"IEND", &[])
+    }
+}
+
+//Writes a frame every --frame-interval steps and on the last step of each generation into the
+//directory given with --frames, which has to exist already
+#[derive(Default)]
+pub struct FrameWriter;
+
+impl FrameWriter {
+    pub fn new() -> FrameWriter {
+        FrameWriter
+    }
+}
+
+impl Observer for FrameWriter {
+    fn step_end(&mut self, context: &Context, report: &StepReport) {
+        let config = context.config;
+        let dir = match config.get_frames_path() {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        let interval = config.get_frame_interval();
+        if report.step + 1 != config.get_steps_per_gen()
+            && (interval == 0 || !(report.step + 1).is_multiple_of(interval))
+        {
+            return;
+        }
+
+        let format = config.get_frame_format();
+        let path = format!(
+            "{}/gen_{:05}_step_{:04}.{}",
+            dir,
+            context.generation,
+            report.step,
+            format.get_name()
+        );
+
+        let frame = Frame::render(config, context.grid, context.population, report.step);
+        if let Err(err) = frame.save(&path, format) {
+            println!("Error: Failed to write {}: {}", path, err);
+        }
+    }
+}
+
+fn blend(base: (u8, u8, u8), tint: (u8, u8, u8), amount: f32) -> (u8, u8, u8) {
+    let mix = |base: u8, tint: u8| (base as f32 + (tint as f32 - base as f32) * amount) as u8;
+    (
+        mix(base.0, tint.0),
+        mix(base.1, tint.1),
+        mix(base.2, tint.2),
+    )
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let mut crc = crc32_update(0xFFFF_FFFF, kind);
+    crc = crc32_update(crc, data);
+    writer.write_all(&(crc ^ 0xFFFF_FFFF).to_be_bytes())
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    crc
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = u16::MAX as usize;
+
+    let mut output = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    output.extend_from_slice(&[0x78, 0x01]);
+
+    let blocks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(MAX_BLOCK).collect()
+    };
+    for (index, block) in blocks.iter().enumerate() {
+        output.push((index == blocks.len() - 1) as u8);
+        output.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{crc32_update, zlib_stored, Frame};
+    use crate::{
+        config::Config,
+        grid::Grid,
+        population::Population,
+        rng::{derive_rng, Stream},
+    };
+
+    #[test]
+    fn checksums() {
+        assert_eq!(
+            crc32_update(0xFFFF_FFFF, b"IEND") ^ 0xFFFF_FFFF,
+            0xAE42_6082
+        );
+
+        let stored = zlib_stored(b"Wikipedia");
+        assert_eq!(&stored[stored.len() - 4..], &0x11E6_0398u32.to_be_bytes());
+
+        //Blocks are split at 65535 bytes with only the last one marked final
+        let stored = zlib_stored(&vec![7; 70000]);
+        assert_eq!(stored[2], 0);
+        assert_eq!(stored[2 + 5 + 65535], 1);
+        assert_eq!(stored.len(), 2 + 70000 + 10 + 4);
+    }
+
+    #[test]
+    fn draws_cells_and_writes_images() {
+        let config = Rc::new(Config::new(10, 4, 16, 8, 0.0, 30, false).unwrap());
+        let mut grid = Grid::new(16, 8);
+        grid.set_barrier(0, 0, true);
+        let population = Population::new(
+            &config,
+            &mut grid,
+            &mut derive_rng(4, Stream::Genesis, 0, 0, 0),
+        );
+
+        let frame = Frame::render(&config, &grid, &population, 0);
+        assert_eq!(frame.get_dimensions(), (512, 256));
+        //The barrier tile is in the bottom left corner of the image
+        assert_eq!(frame.get_pixel(0, 255), super::BARRIER);
+
+        let (x, y) = population.getCellMovementData(3).getCoords();
+        assert_eq!(
+            frame.get_pixel(x * 32 + 5, 255 - y * 32 - 5),
+            population.getCellMiscData(3).color
+        );
+
+        let mut ppm = Vec::new();
+        frame.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n512 256\n255\n"));
+        assert_eq!(ppm.len(), 15 + 512 * 256 * 3);
+
+        let mut png = Vec::new();
+        frame.write_png(&mut png).unwrap();
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G']));
+        assert_eq!(&png[12..16], b"IHDR");
+        assert!(png.ends_with(&[b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    }
+}
@@ -1,9 +1,11 @@
 use std::{
     fmt::{Debug, Display, Write},
+    io::{self, Read},
+    mem::size_of,
     ops::{Add, BitXor, Mul},
 };
 
-use rand::{prelude::ThreadRng, RngCore};
+use rand::RngCore;
 
 use crate::neuron::NeuralNet;
 
@@ -12,48 +14,103 @@ use crate::neuron::NeuralNet;
 pub struct Gene {
     /* Bits 0-15 = Weight -> 16 bits
      * Bits 16-23 = Tail Node -> 8 bits
-     * Bits 24-31 = Head Node -> 8 bits
+     * Bits 24-31 = Head Node -> 8 bits, NodeID::Bias makes the weight a bias of the tail
      */
     pub gene: u32,
 }
 
 impl Gene {
     #[inline(always)]
-    pub fn new(gene: u32) -> Gene {
-        Gene { gene }.normalize()
+    pub fn new(gene: u32, inner_nodes: usize) -> Gene {
+        Gene { gene }.normalize(inner_nodes)
     }
 
     #[inline(always)]
-    pub fn new_random(rng: &mut ThreadRng) -> Gene {
+    pub fn new_random<R: RngCore>(rng: &mut R, inner_nodes: usize) -> Gene {
         Gene {
             gene: rng.next_u32(),
         }
-        .normalize()
+        .normalize(inner_nodes)
     }
 
-    fn normalize(self) -> Gene {
+    #[cfg(test)]
+    pub fn from_parts(head: NodeID, tail: NodeID, weight: i16, inner_nodes: usize) -> Gene {
+        Gene::new(
+            ((head.get_index() as u32) << 24)
+                | (((tail.get_index() - INPUT_NODE_COUNT) as u32) << 16)
+                | weight as u16 as u32,
+            inner_nodes,
+        )
+    }
+
+    //Heads are stored as node ids and tails as node ids less the inputs, so genes decode without
+    //knowing the inner node count
+    //
+    //Valid genes are left as they are, anything else is wrapped onto the nodes that exist, with
+    //one extra head value standing for a bias
+    fn normalize(self, inner_nodes: usize) -> Gene {
         let weight = self.gene & 0xFFFF;
-        let tail = ((self.gene >> 16) & 0xFF) % (INNER_NODE_COUNT + OUTPUT_NODE_COUNT) as u32;
-        let head = ((self.gene >> 24) & 0xFF) % (INPUT_NODE_COUNT + INNER_NODE_COUNT) as u32;
+        let bias = NodeID::Bias.get_index() as u32;
+        let head = match (self.gene >> 24) & 0xFF {
+            head if head == bias => bias,
+            head => match head % (INPUT_NODE_COUNT + inner_nodes + 1) as u32 {
+                head if head as usize == INPUT_NODE_COUNT + inner_nodes => bias,
+                head => head,
+            },
+        };
+
+        let mut tail = ((self.gene >> 16) & 0xFF) as usize;
+        let is_output =
+            (MAX_INNER_NODE_COUNT..MAX_INNER_NODE_COUNT + OUTPUT_NODE_COUNT).contains(&tail);
+        if tail >= inner_nodes && !is_output {
+            tail %= inner_nodes + OUTPUT_NODE_COUNT;
+            if tail >= inner_nodes {
+                tail += MAX_INNER_NODE_COUNT - inner_nodes;
+            }
+        }
+
         Gene {
-            gene: (head << 24) | (tail << 16) | weight,
+            gene: (head << 24) | ((tail as u32) << 16) | weight,
         }
     }
 
     pub fn get_head_node_id(&self) -> NodeID {
-        NodeID::as_head_types((self.gene >> 24) as u8)
+        NodeID((self.gene >> 24) as u8)
     }
 
     pub fn get_tail_node_id(&self) -> NodeID {
-        NodeID::as_tail_types(((self.gene >> 16) & 0xFF) as u8)
+        NodeID(((self.gene >> 16) & 0xFF) as u8 + INPUT_NODE_COUNT as u8)
+    }
+
+    //Bias genes add their weight to the tail neuron instead of connecting two neurons
+    pub fn is_bias(&self) -> bool {
+        self.get_head_node_id().is_bias()
     }
 
     pub fn get_weight(&self) -> f32 {
         (self.gene as i16 as f32) / ((u16::MAX / 8) as f32)
     }
 
-    pub fn get_connection_index(&self) -> usize {
-        NeuralNet::get_connection_index(self.get_head_node_id(), self.get_tail_node_id())
+    //Only for genes that aren't biases
+    pub fn get_connection_index(&self, inner_nodes: usize) -> usize {
+        debug_assert!(!self.is_bias());
+        NeuralNet::get_connection_index(
+            self.get_head_node_id(),
+            self.get_tail_node_id(),
+            inner_nodes,
+        )
+    }
+
+    pub fn serialize<T: io::Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_all(&self.gene.to_le_bytes())
+    }
+
+    //Genes are normalized on load, so corrupted data cannot produce invalid node ids
+    pub fn deserialize<T: Read>(reader: &mut T, inner_nodes: usize) -> io::Result<Self> {
+        let mut buf4 = [0; size_of::<u32>()];
+        reader.read_exact(&mut buf4)?;
+
+        Ok(Gene::new(u32::from_le_bytes(buf4), inner_nodes))
     }
 }
 
@@ -85,34 +142,64 @@ impl Debug for Gene {
     }
 }
 
-pub const INPUT_NODE_COUNT: usize = 4;
-pub const INNER_NODE_COUNT: usize = 3;
+pub const INPUT_NODE_COUNT: usize = 8;
 pub const OUTPUT_NODE_COUNT: usize = 10;
-pub const TOTAL_NODE_COUNT: usize = INPUT_NODE_COUNT + INNER_NODE_COUNT + OUTPUT_NODE_COUNT;
+pub const DEFAULT_INNER_NODE_COUNT: usize = 3;
+//Every inner node up to this has its own id, so the ids of the outputs don't depend on the config
+pub const MAX_INNER_NODE_COUNT: usize = 64;
+
+const INPUT_NAMES: [&str; INPUT_NODE_COUNT] = [
+    "DistX",
+    "DistY",
+    "Age",
+    "Oscillator",
+    "PopDensity",
+    "BlockedForward",
+    "DistToCellForward",
+    "DistToWallForward",
+];
+const OUTPUT_NAMES: [&str; OUTPUT_NODE_COUNT] = [
+    "MoveNorth",
+    "MoveEast",
+    "MoveSouth",
+    "MoveWest",
+    "MoveRandom",
+    "MoveForward",
+    "MoveRight",
+    "MoveLeft",
+    "MoveReverse",
+    "KillForward",
+];
+
+//Ids are inputs, then every possible inner node, then outputs
+//
+//Networks and statistics only hold the nodes that exist, in the same order, see get_layout_index
+#[derive(Clone, Copy, PartialEq, PartialOrd, Ord, Eq)]
+pub struct NodeID(u8);
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq)]
-pub enum NodeID {
+impl NodeID {
     //Input Nodes
-    DistX = 0,
-    DistY,
-    Age,
-    Oscillator,
-    //Inner Nodes
-    Inner1,
-    Inner2,
-    Inner3,
+    pub const DistX: NodeID = NodeID(0);
+    pub const DistY: NodeID = NodeID(1);
+    pub const Age: NodeID = NodeID(2);
+    pub const Oscillator: NodeID = NodeID(3);
+    pub const PopDensity: NodeID = NodeID(4);
+    pub const BlockedForward: NodeID = NodeID(5);
+    pub const DistToCellForward: NodeID = NodeID(6);
+    pub const DistToWallForward: NodeID = NodeID(7);
     //Output Nodes
-    MoveNorth,
-    MoveEast,
-    MoveSouth,
-    MoveWest,
-    MoveRandom,
-    MoveForward,
-    MoveRight,
-    MoveLeft,
-    MoveReverse,
-    KillForward,
-    End,
+    pub const MoveNorth: NodeID = NodeID::as_output(0);
+    pub const MoveEast: NodeID = NodeID::as_output(1);
+    pub const MoveSouth: NodeID = NodeID::as_output(2);
+    pub const MoveWest: NodeID = NodeID::as_output(3);
+    pub const MoveRandom: NodeID = NodeID::as_output(4);
+    pub const MoveForward: NodeID = NodeID::as_output(5);
+    pub const MoveRight: NodeID = NodeID::as_output(6);
+    pub const MoveLeft: NodeID = NodeID::as_output(7);
+    pub const MoveReverse: NodeID = NodeID::as_output(8);
+    pub const KillForward: NodeID = NodeID::as_output(9);
+    //Head of bias genes, a constant source rather than a node of the network
+    pub const Bias: NodeID = NodeID(u8::MAX);
 }
 
 impl Add<usize> for NodeID {
@@ -131,6 +218,20 @@ impl Mul<usize> for NodeID {
     }
 }
 
+impl Debug for NodeID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_bias() {
+            f.write_str("Bias")
+        } else if self.is_input() {
+            f.write_str(INPUT_NAMES[self.get_input_index()])
+        } else if self.is_inner() {
+            write!(f, "Inner{}", self.get_inner_index() + 1)
+        } else {
+            f.write_str(OUTPUT_NAMES[self.get_output_index()])
+        }
+    }
+}
+
 impl Display for NodeID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut string = String::new();
@@ -139,64 +240,49 @@ impl Display for NodeID {
     }
 }
 
-use self::NodeID::*;
-
 impl NodeID {
-    const fn to_int(self) -> usize {
-        match self {
-            DistX => 0,
-            DistY => 1,
-            Age => 2,
-            Oscillator => 3,
-            Inner1 => 4,
-            Inner2 => 5,
-            Inner3 => 6,
-            MoveNorth => 7,
-            MoveEast => 8,
-            MoveSouth => 9,
-            MoveWest => 10,
-            MoveRandom => 11,
-            MoveForward => 12,
-            MoveRight => 13,
-            MoveLeft => 14,
-            MoveReverse => 15,
-            KillForward => 16,
-            End => unimplemented!(),
-        }
+    pub const fn get_node_count(inner_nodes: usize) -> usize {
+        INPUT_NODE_COUNT + inner_nodes + OUTPUT_NODE_COUNT
     }
 
-    pub const fn get_max_connections() -> usize {
-        INPUT_NODE_COUNT * INNER_NODE_COUNT
+    pub const fn get_max_connections(inner_nodes: usize) -> usize {
+        INPUT_NODE_COUNT * inner_nodes
             + INPUT_NODE_COUNT * OUTPUT_NODE_COUNT
-            + INNER_NODE_COUNT * INNER_NODE_COUNT
-            + INNER_NODE_COUNT * OUTPUT_NODE_COUNT
+            + inner_nodes * inner_nodes
+            + inner_nodes * OUTPUT_NODE_COUNT
     }
 
-    pub const fn get_index(&self) -> usize {
-        (*self) as i32 as usize
+    //Every node of a network with inner_nodes inner nodes, in layout order
+    pub fn get_nodes(inner_nodes: usize) -> impl Iterator<Item = NodeID> {
+        (0..NodeID::get_node_count(inner_nodes))
+            .map(move |index| NodeID::from_layout_index(index, inner_nodes))
     }
 
-    pub fn from_index(index: usize) -> NodeID {
-        assert!(index < TOTAL_NODE_COUNT);
-
-        unsafe { NodeID::from_index_unchecked(index) }
+    pub const fn get_index(&self) -> usize {
+        self.0 as usize
     }
 
-    #[allow(unused)]
-    unsafe fn from_index_unchecked(index: usize) -> NodeID {
-        unsafe { std::mem::transmute::<u8, NodeID>(index as u8) }
+    //Position of the node among the nodes that exist with inner_nodes inner nodes
+    pub const fn get_layout_index(&self, inner_nodes: usize) -> usize {
+        if self.is_output() {
+            self.get_output_index() + INPUT_NODE_COUNT + inner_nodes
+        } else {
+            self.get_index()
+        }
     }
 
-    fn as_head_types(value: u8) -> NodeID {
-        unsafe { std::mem::transmute(value) }
-    }
+    pub fn from_layout_index(index: usize, inner_nodes: usize) -> NodeID {
+        assert!(index < NodeID::get_node_count(inner_nodes));
 
-    fn as_tail_types(value: u8) -> NodeID {
-        unsafe { std::mem::transmute(value + (INPUT_NODE_COUNT as u8)) }
+        if index < INPUT_NODE_COUNT + inner_nodes {
+            NodeID(index as u8)
+        } else {
+            NodeID::as_output(index - INPUT_NODE_COUNT - inner_nodes)
+        }
     }
 
     pub const fn get_output_index(&self) -> usize {
-        self.get_index() - (INNER_NODE_COUNT + INPUT_NODE_COUNT)
+        self.get_index() - (MAX_INNER_NODE_COUNT + INPUT_NODE_COUNT)
     }
 
     pub const fn get_input_index(&self) -> usize {
@@ -207,53 +293,95 @@ impl NodeID {
         self.get_index() - INPUT_NODE_COUNT
     }
 
-    pub fn as_input(value: usize) -> NodeID {
-        NodeID::from_index(value)
+    pub const fn as_input(value: usize) -> NodeID {
+        assert!(value < INPUT_NODE_COUNT);
+        NodeID(value as u8)
     }
 
-    pub fn as_inner(value: usize) -> NodeID {
-        NodeID::from_index(value + INPUT_NODE_COUNT)
+    pub const fn as_inner(value: usize) -> NodeID {
+        assert!(value < MAX_INNER_NODE_COUNT);
+        NodeID((value + INPUT_NODE_COUNT) as u8)
     }
 
-    pub fn as_output(value: usize) -> NodeID {
-        NodeID::from_index(value + INPUT_NODE_COUNT + INNER_NODE_COUNT)
+    pub const fn as_output(value: usize) -> NodeID {
+        assert!(value < OUTPUT_NODE_COUNT);
+        NodeID((value + INPUT_NODE_COUNT + MAX_INNER_NODE_COUNT) as u8)
     }
 
     pub const fn is_input(&self) -> bool {
-        self.to_int() < NodeID::Inner1.to_int()
+        self.get_index() < INPUT_NODE_COUNT
     }
 
     pub const fn is_inner(&self) -> bool {
-        self.to_int() > NodeID::Oscillator.to_int() && self.to_int() < NodeID::MoveNorth.to_int()
+        self.get_index() >= INPUT_NODE_COUNT
+            && self.get_index() < INPUT_NODE_COUNT + MAX_INNER_NODE_COUNT
     }
 
     pub const fn is_output(&self) -> bool {
-        self.to_int() < NodeID::End.to_int() && self.to_int() > NodeID::Inner3.to_int()
+        self.get_index() >= INPUT_NODE_COUNT + MAX_INNER_NODE_COUNT
+            && self.get_index() < INPUT_NODE_COUNT + MAX_INNER_NODE_COUNT + OUTPUT_NODE_COUNT
+    }
+
+    pub const fn is_bias(&self) -> bool {
+        self.get_index() == NodeID::Bias.get_index()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::NodeID::*;
+    use super::{Gene, NodeID, INPUT_NODE_COUNT, MAX_INNER_NODE_COUNT, OUTPUT_NODE_COUNT};
 
     #[test]
-    fn compare_const_and_runtime() {
-        assert!(DistX.to_int() == DistX.get_index());
-        assert!(DistY.to_int() == DistY.get_index());
-        assert!(Age.to_int() == Age.get_index());
-        assert!(Oscillator.to_int() == Oscillator.get_index());
-        assert!(Inner1.to_int() == Inner1.get_index());
-        assert!(Inner2.to_int() == Inner2.get_index());
-        assert!(Inner3.to_int() == Inner3.get_index());
-        assert!(MoveNorth.to_int() == MoveNorth.get_index());
-        assert!(MoveEast.to_int() == MoveEast.get_index());
-        assert!(MoveSouth.to_int() == MoveSouth.get_index());
-        assert!(MoveWest.to_int() == MoveWest.get_index());
-        assert!(MoveRandom.to_int() == MoveRandom.get_index());
-        assert!(MoveForward.to_int() == MoveForward.get_index());
-        assert!(MoveRight.to_int() == MoveRight.get_index());
-        assert!(MoveLeft.to_int() == MoveLeft.get_index());
-        assert!(MoveReverse.to_int() == MoveReverse.get_index());
-        assert!(KillForward.to_int() == KillForward.get_index());
+    fn node_layout() {
+        assert_eq!(NodeID::DistToWallForward.get_index(), INPUT_NODE_COUNT - 1);
+        assert_eq!(NodeID::MoveNorth.get_output_index(), 0);
+        assert_eq!(
+            NodeID::KillForward.get_output_index(),
+            OUTPUT_NODE_COUNT - 1
+        );
+        assert!(NodeID::KillForward.is_output() && NodeID::MoveNorth.is_output());
+        assert!(NodeID::as_inner(2).is_inner() && !NodeID::as_inner(2).is_output());
+
+        for inner_nodes in [0, 3, MAX_INNER_NODE_COUNT] {
+            let nodes: Vec<NodeID> = NodeID::get_nodes(inner_nodes).collect();
+            assert_eq!(nodes.len(), NodeID::get_node_count(inner_nodes));
+            assert_eq!(
+                nodes.iter().filter(|node| node.is_inner()).count(),
+                inner_nodes
+            );
+            assert_eq!(nodes[nodes.len() - 1], NodeID::KillForward);
+
+            for (index, node) in nodes.iter().enumerate() {
+                assert_eq!(node.get_layout_index(inner_nodes), index);
+            }
+        }
+
+        assert_eq!(format!("{:?}", NodeID::as_inner(0)), "Inner1");
+        assert_eq!(format!("{:?}", NodeID::MoveEast), "MoveEast");
+        assert_eq!(format!("{:?}", NodeID::Bias), "Bias");
+        assert!(!NodeID::Bias.is_output() && !NodeID::Bias.is_inner());
+    }
+
+    #[test]
+    fn genes_decode_to_existing_nodes() {
+        for inner_nodes in [0, 1, 3, 17, MAX_INNER_NODE_COUNT] {
+            for raw in (0..=u32::MAX).step_by(0x0001_3579) {
+                let gene = Gene::new(raw, inner_nodes);
+                let (head, tail) = (gene.get_head_node_id(), gene.get_tail_node_id());
+
+                assert!(head.is_input() || head.is_bias() || head.get_inner_index() < inner_nodes);
+                assert!(!head.is_output() && !tail.is_input());
+                assert!(tail.is_output() || tail.get_inner_index() < inner_nodes);
+                assert_eq!(gene.is_bias(), head.is_bias());
+                //Normalizing again doesn't change a valid gene
+                assert_eq!(Gene::new(gene.gene, inner_nodes).gene, gene.gene);
+            }
+        }
+
+        //The head value past the inner nodes wraps onto the bias
+        let tail = (MAX_INNER_NODE_COUNT as u32) << 16;
+        assert!(Gene::new(((INPUT_NODE_COUNT + 3) as u32) << 24 | tail, 3).is_bias());
+        assert!(Gene::new(0xFF00_0000 | tail, 3).is_bias());
+        assert!(!Gene::new(((INPUT_NODE_COUNT + 2) as u32) << 24 | tail, 3).is_bias());
     }
 }
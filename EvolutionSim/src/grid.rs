@@ -6,6 +6,8 @@ pub struct Grid {
     width: GridValueT,
     height: GridValueT,
     grid: Box<[Option<usize>]>,
+    //Tiles that can never be occupied, kept across resets
+    barriers: Box<[bool]>,
 }
 
 impl Grid {
@@ -15,6 +17,7 @@ impl Grid {
             width,
             height,
             grid: grid.into_boxed_slice(),
+            barriers: vec![false; width * height].into_boxed_slice(),
         }
     }
 
@@ -26,51 +29,163 @@ impl Grid {
         self.grid[x + y * self.width] = cell;
     }
 
-    pub fn get_in_radius(&self, coords: (GridValueT, GridValueT), radius: f32) -> Vec<usize> {
-        println!("Center: ({}, {})", coords.0, coords.1);
-        let top = (coords.1 as f32 + radius) as GridValueT;
-        let bottom = (coords.1 as f32 - radius) as GridValueT;
+    pub fn is_barrier(&self, x: GridValueT, y: GridValueT) -> bool {
+        self.barriers[x + y * self.width]
+    }
+
+    pub fn set_barrier(&mut self, x: GridValueT, y: GridValueT, barrier: bool) {
+        self.barriers[x + y * self.width] = barrier;
+    }
+
+    //Neither occupied by a cell nor a barrier
+    pub fn is_free(&self, x: GridValueT, y: GridValueT) -> bool {
+        !self.is_barrier(x, y) && self.get_occupant(x, y).is_none()
+    }
+
+    pub fn get_free_count(&self) -> usize {
+        (0..self.grid.len())
+            .filter(|&index| !self.barriers[index] && self.grid[index].is_none())
+            .count()
+    }
 
+    pub fn get_barrier_coords(&self) -> Vec<(GridValueT, GridValueT)> {
+        (0..self.barriers.len())
+            .filter(|&index| self.barriers[index])
+            .map(|index| (index % self.width, index / self.width))
+            .collect()
+    }
+
+    //Every occupant whose tile center lies within radius of coords, including the cell at coords
+    pub fn get_in_radius(&self, coords: (GridValueT, GridValueT), radius: f32) -> Vec<usize> {
         let mut in_radius = Vec::new();
 
-        let mut cy = bottom;
-        while cy <= top {
-            let dy = cy.saturating_sub(coords.1);
-            let dx = (radius * radius - (dy * dy) as f32).sqrt();
-
-            let left = (coords.0 as f32 - dx).ceil() as GridValueT;
-            let right = (coords.0 as f32 + dx).ceil() as GridValueT;
-
-            let mut cx = left;
-            while cx <= right {
-                let occupant = self.grid[cx + cy * self.width];
-                if let Some(cell_index) = occupant {
-                    in_radius.push(cell_index);
-                }
-                cx += 1;
+        self.for_each_in_radius(coords, radius, |occupant| {
+            if let Some(cell_index) = occupant {
+                in_radius.push(cell_index);
             }
+        });
+
+        in_radius
+    }
 
-            cy += 1;
+    //Fraction of the other tiles within radius that are occupied, the tile at coords is not counted
+    pub fn get_density(&self, coords: (GridValueT, GridValueT), radius: f32) -> f32 {
+        let (mut tiles, mut occupied) = (0usize, 0usize);
+
+        self.for_each_in_radius(coords, radius, |occupant| {
+            tiles += 1;
+            if occupant.is_some() {
+                occupied += 1;
+            }
+        });
+
+        let (tiles, occupied) = (
+            tiles - 1,
+            occupied - self.get_occupant(coords.0, coords.1).is_some() as usize,
+        );
+        if tiles == 0 {
+            0.0
+        } else {
+            occupied as f32 / tiles as f32
         }
+    }
 
-        in_radius
+    //Tiles outside of the grid and barriers count as blocked
+    pub fn is_blocked(&self, x: isize, y: isize) -> bool {
+        !self.contains(x, y) || !self.is_free(x as GridValueT, y as GridValueT)
+    }
+
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        x >= 0 && y >= 0 && x < self.width as isize && y < self.height as isize
+    }
+
+    //Steps taken from coords along offset before reaching an occupied tile, if one is found within length steps
+    //The ray stops at barriers and the edge of the grid
+    pub fn dist_to_occupied(
+        &self,
+        coords: (GridValueT, GridValueT),
+        offset: (isize, isize),
+        length: usize,
+    ) -> Option<usize> {
+        let (mut x, mut y) = (coords.0 as isize, coords.1 as isize);
+
+        for distance in 1..=length {
+            x += offset.0;
+            y += offset.1;
+
+            if !self.contains(x, y) || self.is_barrier(x as GridValueT, y as GridValueT) {
+                return None;
+            }
+            if self
+                .get_occupant(x as GridValueT, y as GridValueT)
+                .is_some()
+            {
+                return Some(distance);
+            }
+        }
+
+        None
+    }
+
+    //Steps taken from coords along offset before reaching a barrier or leaving the grid
+    pub fn dist_to_wall(&self, coords: (GridValueT, GridValueT), offset: (isize, isize)) -> usize {
+        if offset == (0, 0) {
+            return usize::MAX;
+        }
+
+        let (mut x, mut y) = (coords.0 as isize, coords.1 as isize);
+        let mut distance = 0;
+
+        loop {
+            x += offset.0;
+            y += offset.1;
+            distance += 1;
+
+            if !self.contains(x, y) || self.is_barrier(x as GridValueT, y as GridValueT) {
+                return distance;
+            }
+        }
+    }
+
+    //Visits the tiles of the circle row by row, clipped to the grid
+    fn for_each_in_radius<F: FnMut(Option<usize>)>(
+        &self,
+        coords: (GridValueT, GridValueT),
+        radius: f32,
+        mut f: F,
+    ) {
+        let (x, y) = (coords.0 as isize, coords.1 as isize);
+        let reach = radius.max(0.0).floor() as isize;
+
+        let bottom = (y - reach).max(0);
+        let top = (y + reach).min(self.height as isize - 1);
+
+        for cy in bottom..=top {
+            let dy = cy - y;
+            let dx = (radius * radius - (dy * dy) as f32).sqrt().floor() as isize;
+
+            let left = (x - dx).max(0);
+            let right = (x + dx).min(self.width as isize - 1);
+
+            for cx in left..=right {
+                f(self.grid[cx as usize + cy as usize * self.width]);
+            }
+        }
     }
 
     pub fn reset(&mut self) {
         self.grid.fill_with(|| None);
     }
 
-    pub fn find_random_unoccupied(&self) -> (usize, usize) {
+    pub fn find_random_unoccupied<R: Rng>(&self, rng: &mut R) -> (usize, usize) {
         let mut x;
         let mut y;
 
-        let mut rng = rand::thread_rng();
-
         loop {
             x = rng.gen_range(0..self.width);
             y = rng.gen_range(0..self.height);
 
-            if self.get_occupant(x, y).is_none() {
+            if self.is_free(x, y) {
                 break;
             }
         }
@@ -91,3 +206,53 @@ impl std::fmt::Debug for Grid {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Grid;
+
+    #[test]
+    fn in_radius_clipped_to_grid() {
+        let mut grid = Grid::new(5, 5);
+        for x in 0..5 {
+            for y in 0..5 {
+                grid.set_occupant(x, y, Some(x + y * 5));
+            }
+        }
+
+        let mut corner = grid.get_in_radius((0, 0), 1.5);
+        corner.sort_unstable();
+        assert_eq!(corner, vec![0, 1, 5, 6]);
+
+        let mut center = grid.get_in_radius((2, 2), 1.0);
+        center.sort_unstable();
+        assert_eq!(center, vec![7, 11, 12, 13, 17]);
+
+        assert_eq!(grid.get_in_radius((4, 4), 10.0).len(), 25);
+        assert_eq!(grid.get_density((4, 4), 1.5), 1.0);
+
+        grid.set_occupant(3, 3, None);
+        grid.set_occupant(4, 3, None);
+        assert_eq!(grid.get_density((4, 4), 1.5), 1.0 / 3.0);
+        assert_eq!(grid.get_density((4, 4), 0.5), 0.0);
+    }
+
+    #[test]
+    fn rays() {
+        let mut grid = Grid::new(6, 4);
+        grid.set_occupant(4, 1, Some(0));
+
+        assert_eq!(grid.dist_to_occupied((0, 1), (1, 0), 8), Some(4));
+        assert_eq!(grid.dist_to_occupied((0, 1), (1, 0), 3), None);
+        assert_eq!(grid.dist_to_occupied((0, 1), (-1, 0), 8), None);
+        assert_eq!(grid.dist_to_occupied((2, 3), (1, -1), 8), Some(2));
+
+        assert_eq!(grid.dist_to_wall((0, 1), (1, 0)), 6);
+        assert_eq!(grid.dist_to_wall((0, 1), (-1, 0)), 1);
+        assert_eq!(grid.dist_to_wall((2, 1), (1, 1)), 3);
+
+        assert!(grid.is_blocked(-1, 0));
+        assert!(grid.is_blocked(4, 1));
+        assert!(!grid.is_blocked(3, 1));
+    }
+}
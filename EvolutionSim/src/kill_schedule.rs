@@ -0,0 +1,351 @@
+use std::fmt::Display;
+
+use rand::Rng;
+
+use crate::{grid::GridValueT, spec::Spec, TimeT};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    Step(TimeT),
+    //Fraction of the steps in a generation
+    Fraction(f32),
+}
+
+impl Trigger {
+    pub fn get_step(&self, steps_per_gen: TimeT) -> TimeT {
+        match *self {
+            Trigger::Step(step) => step,
+            Trigger::Fraction(fraction) => (fraction * steps_per_gen as f32) as TimeT,
+        }
+    }
+
+    //Integers are absolute steps, anything with a decimal point is a fraction of the generation
+    //
+    //A fraction of 1 is rejected, it would be the step after the generation ends
+    pub fn parse(input: &str) -> Result<Trigger, String> {
+        if input.contains('.') {
+            match input.parse::<f32>() {
+                Ok(fraction) if (0.0..1.0).contains(&fraction) => Ok(Trigger::Fraction(fraction)),
+                _ => Err(format!("Invalid generation fraction `{}`", input)),
+            }
+        } else {
+            input
+                .parse::<TimeT>()
+                .map(Trigger::Step)
+                .map_err(|_| format!("Invalid step `{}`", input))
+        }
+    }
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trigger::Step(step) => write!(f, "{}", step),
+            Trigger::Fraction(fraction) => write!(f, "{:?}", fraction),
+        }
+    }
+}
+
+//Positions are normalized to [0, 1] on both axes, with (0, 0) at the south-west corner
+#[derive(Clone, Debug, PartialEq)]
+pub enum Zone {
+    Rectangle { min: (f32, f32), max: (f32, f32) },
+    Circle { center: (f32, f32), radius: f32 },
+    //Every point where normal . point >= offset
+    HalfPlane { normal: (f32, f32), offset: f32 },
+    Inverse(Box<Zone>),
+}
+
+impl Zone {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        match self {
+            Zone::Rectangle { min, max } => x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1,
+            Zone::Circle { center, radius } => {
+                let (dx, dy) = (x - center.0, y - center.1);
+                dx * dx + dy * dy <= radius * radius
+            }
+            Zone::HalfPlane { normal, offset } => normal.0 * x + normal.1 * y >= *offset,
+            Zone::Inverse(zone) => !zone.contains(x, y),
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Zone, String> {
+        Zone::from_spec(&Spec::parse(input)?)
+    }
+
+    fn from_spec(spec: &Spec) -> Result<Zone, String> {
+        match spec.name.as_str() {
+            "rect" | "rectangle" => {
+                spec.expect_args(4)?;
+                Ok(Zone::Rectangle {
+                    min: (spec.number(0)?, spec.number(1)?),
+                    max: (spec.number(2)?, spec.number(3)?),
+                })
+            }
+            "circle" => {
+                spec.expect_args(3)?;
+                Ok(Zone::Circle {
+                    center: (spec.number(0)?, spec.number(1)?),
+                    radius: spec.number(2)?,
+                })
+            }
+            "halfplane" => {
+                spec.expect_args(3)?;
+                Ok(Zone::HalfPlane {
+                    normal: (spec.number(0)?, spec.number(1)?),
+                    offset: spec.number(2)?,
+                })
+            }
+            "inverse" => {
+                spec.expect_args(1)?;
+                Ok(Zone::Inverse(Box::new(Zone::from_spec(&spec.call(0)?)?)))
+            }
+            name => Err(format!("Unknown zone `{}`", name)),
+        }
+    }
+}
+
+impl Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Zone::Rectangle { min, max } => {
+                write!(f, "rect({}, {}, {}, {})", min.0, min.1, max.0, max.1)
+            }
+            Zone::Circle { center, radius } => {
+                write!(f, "circle({}, {}, {})", center.0, center.1, radius)
+            }
+            Zone::HalfPlane { normal, offset } => {
+                write!(f, "halfplane({}, {}, {})", normal.0, normal.1, offset)
+            }
+            Zone::Inverse(zone) => write!(f, "inverse({})", zone),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KillEvent {
+    pub trigger: Trigger,
+    pub zone: Zone,
+    //Chance that a cell inside the zone dies
+    pub probability: f32,
+}
+
+impl KillEvent {
+    //Format: `trigger zone [probability]`, eg. `0.5 circle(0.5, 0.5, 0.25) 0.8`
+    pub fn parse(line: &str) -> Result<KillEvent, String> {
+        let line = line.trim();
+        let (trigger, rest) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("Missing zone in `{}`", line))?;
+
+        let zone_end = rest
+            .rfind(')')
+            .ok_or_else(|| format!("Missing zone in `{}`", line))?;
+        let (zone, probability) = rest.split_at(zone_end + 1);
+
+        let probability = match probability.trim() {
+            "" => 1.0,
+            probability => match probability.parse::<f32>() {
+                Ok(probability) if (0.0..=1.0).contains(&probability) => probability,
+                _ => return Err(format!("Invalid kill probability `{}`", probability)),
+            },
+        };
+
+        Ok(KillEvent {
+            trigger: Trigger::parse(trigger)?,
+            zone: Zone::parse(zone)?,
+            probability,
+        })
+    }
+
+    pub fn is_triggered(&self, step: TimeT, steps_per_gen: TimeT) -> bool {
+        self.trigger.get_step(steps_per_gen) == step
+    }
+
+    pub fn kills<R: Rng>(&self, x: f32, y: f32, rng: &mut R) -> bool {
+        self.zone.contains(x, y) && (self.probability >= 1.0 || rng.gen::<f32>() < self.probability)
+    }
+}
+
+impl Display for KillEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.trigger, self.zone)?;
+        if self.probability < 1.0 {
+            write!(f, " {}", self.probability)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KillSchedule {
+    events: Vec<KillEvent>,
+}
+
+impl KillSchedule {
+    pub fn new(events: Vec<KillEvent>) -> KillSchedule {
+        KillSchedule { events }
+    }
+
+    //One event per line, blank lines and lines starting with # are ignored
+    pub fn parse(input: &str) -> Result<KillSchedule, String> {
+        let mut events = Vec::new();
+
+        for (line_number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            events.push(
+                KillEvent::parse(line)
+                    .map_err(|err| format!("Line {}: {}", line_number + 1, err))?,
+            );
+        }
+
+        Ok(KillSchedule { events })
+    }
+
+    pub fn load(path: &str) -> Result<KillSchedule, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+        KillSchedule::parse(&contents)
+    }
+
+    pub fn get_events(&self) -> &[KillEvent] {
+        &self.events
+    }
+
+    //The original culls, both outer quarters at 1/4 and 3/4 of a generation, and the middle half at
+    //1/2
+    //
+    //Like the original the quarters come from integer division of the width, and the tiles on their
+    //boundaries survive both culls, so the middle zone is half a tile smaller on each side
+    pub fn original(grid_width: GridValueT) -> KillSchedule {
+        let (west, east) = (grid_width / 4, 3 * grid_width / 4);
+        let width = grid_width as f32;
+
+        let outer = Zone::Inverse(Box::new(Zone::Rectangle {
+            min: (west as f32 / width, 0.0),
+            max: (east as f32 / width, 1.0),
+        }));
+        let middle = Zone::Rectangle {
+            min: ((west as f32 + 0.5) / width, 0.0),
+            max: ((east as f32 - 0.5) / width, 1.0),
+        };
+
+        KillSchedule::new(vec![
+            KillEvent {
+                trigger: Trigger::Fraction(0.25),
+                zone: outer.clone(),
+                probability: 1.0,
+            },
+            KillEvent {
+                trigger: Trigger::Fraction(0.5),
+                zone: middle,
+                probability: 1.0,
+            },
+            KillEvent {
+                trigger: Trigger::Fraction(0.75),
+                zone: outer,
+                probability: 1.0,
+            },
+        ])
+    }
+}
+
+impl Display for KillSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KillSchedule, Trigger, Zone};
+    use crate::selection::normalize_coords;
+
+    #[test]
+    fn parse_schedule() {
+        let schedule = KillSchedule::parse(
+            "# comment\n\n10 inverse(circle(0.5, 0.5, 0.25))\n0.5 halfplane(1, 0, 0.5) 0.25\n",
+        )
+        .unwrap();
+
+        let events = schedule.get_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].trigger, Trigger::Step(10));
+        assert_eq!(events[0].probability, 1.0);
+        assert!(events[0].zone.contains(0.0, 0.0));
+        assert!(!events[0].zone.contains(0.5, 0.5));
+        assert_eq!(events[1].trigger.get_step(300), 150);
+        assert_eq!(events[1].probability, 0.25);
+        assert!(events[1].zone.contains(0.75, 0.1));
+        assert!(!events[1].zone.contains(0.25, 0.1));
+
+        assert_eq!(
+            KillSchedule::parse(&schedule.to_string()).unwrap(),
+            schedule
+        );
+        assert_eq!(
+            KillSchedule::parse(&KillSchedule::original(200).to_string()).unwrap(),
+            KillSchedule::original(200)
+        );
+    }
+
+    #[test]
+    fn original_culls() {
+        for width in [1, 2, 7, 10, 13, 200, 201] {
+            let schedule = KillSchedule::original(width);
+            let events = schedule.get_events();
+            assert_eq!(
+                events
+                    .iter()
+                    .map(|event| event.trigger.get_step(250))
+                    .collect::<Vec<_>>(),
+                vec![62, 125, 187]
+            );
+
+            let mut rng = rand::thread_rng();
+            for x in 0..width {
+                let (nx, ny) = normalize_coords((x, 5), (width, 10));
+                let outer = x < width / 4 || x > 3 * width / 4;
+                let middle = x > width / 4 && x < 3 * width / 4;
+
+                assert_eq!(
+                    events[0].kills(nx, ny, &mut rng),
+                    outer,
+                    "x {} of {}",
+                    x,
+                    width
+                );
+                assert_eq!(
+                    events[1].kills(nx, ny, &mut rng),
+                    middle,
+                    "x {} of {}",
+                    x,
+                    width
+                );
+                assert_eq!(
+                    events[2].kills(nx, ny, &mut rng),
+                    outer,
+                    "x {} of {}",
+                    x,
+                    width
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(KillSchedule::parse("0.5").is_err());
+        assert!(KillSchedule::parse("1.5 circle(0, 0, 1)").is_err());
+        assert!(KillSchedule::parse("1.0 circle(0, 0, 1)").is_err());
+        assert!(KillSchedule::parse("10 circle(0, 0, 1) 2").is_err());
+        assert!(Zone::parse("square(1)").is_err());
+    }
+}
@@ -0,0 +1,449 @@
+#![allow(non_snake_case, non_upper_case_globals, temporary_cstring_as_ptr)]
+#![feature(trace_macros, new_uninit)]
+#![feature(test)]
+
+use std::rc::Rc;
+use std::slice::{Chunks, ChunksMut};
+
+extern crate rand;
+
+extern crate custom_dst;
+
+extern crate scoped_threadpool;
+use cell::NeuronData;
+use scoped_threadpool::Pool;
+
+pub mod windowed;
+
+pub mod grid;
+use grid::Grid;
+
+pub mod population;
+use population::{MoveRequest, Population};
+
+pub mod cell;
+
+pub mod gene;
+
+pub mod neuron;
+
+mod bench;
+
+pub mod snapshot;
+
+pub mod rng;
+use rng::{derive_rng, Stream};
+
+pub mod spec;
+
+pub mod selection;
+
+pub mod kill_schedule;
+
+pub mod barrier;
+
+pub mod dot;
+
+pub mod frame;
+
+pub mod recording;
+
+pub mod stats;
+
+pub mod observer;
+
+pub mod simulation;
+pub use simulation::Simulation;
+
+pub mod config;
+use config::Config as ConfigBase;
+
+pub type Config = Rc<ConfigBase>;
+pub type TimeT = usize;
+
+pub fn computeMovements(
+    config: &Config,
+    threadpool: &mut Pool,
+    pop: &mut Population,
+    grid: &Grid,
+    generation: TimeT,
+    step: TimeT,
+) -> usize {
+    let living = pop.getLivingIndices();
+    let len = living.len();
+
+    let parts = {
+        let threads = threadpool.thread_count();
+        let (num, rem) = (
+            living.len() / ((threads + 1) as usize),
+            living.len() % ((threads + 1) as usize),
+        );
+
+        if rem != 0 {
+            num + 1
+        } else {
+            num
+        }
+    };
+
+    let (movement, neuron, heritable, _, results) = pop.get_data_mut();
+
+    //General read-only data
+    let (movement, heritable) = { (&*movement, &heritable.as_shared_slice()) };
+
+    let mut neuron = Some(neuron);
+
+    //Thread IO is chunked for lock-free reading and writing
+    let mut living: Chunks<usize> = living.as_slice().chunks(parts);
+    let mut resChunks: ChunksMut<MoveRequest> = results.chunks_mut(parts);
+
+    let stepsPerGen = config.get_steps_per_gen();
+    let seed = config.get_seed();
+    let killEnabled = config.get_kill_enabled();
+    let densityRadius = config.get_density_radius();
+    let sightDistance = config.get_sight_distance();
+
+    threadpool.scoped(|scope| {
+        //Chunked IO for main thread
+        let local_living = living.next().unwrap();
+        let localResults = resChunks.next().unwrap();
+
+        let mut last_included_cell = local_living.last().unwrap();
+        let mut start_index;
+
+        //# Safety
+        //
+        // neuron is declared as a some above, so it must be a some
+        let local_neuron = unsafe {
+            let (first, last) = split_or_get(neuron.unwrap_unchecked(), last_included_cell + 1);
+            //We add two to get the correct index because the first slice doesn't include the index
+            neuron = last;
+            first
+        };
+
+        for living_chunk in living {
+            start_index = last_included_cell + 1;
+            last_included_cell = living_chunk.last().unwrap();
+
+            let neuron_chunk = {
+                //# Safety
+                //
+                //The splitting values will only be equal to the length of the chunk at the end,
+                //and so it will always be Some
+                let (fst, lst) = unsafe {
+                    split_or_get(
+                        neuron.unwrap_unchecked(),
+                        last_included_cell - start_index + 1,
+                    )
+                };
+                neuron = lst;
+                fst
+            };
+            let resChunk = resChunks.next().unwrap();
+
+            scope.execute(move || {
+                for (index, cellIndex) in living_chunk.iter().enumerate() {
+                    let movement = &movement[*cellIndex];
+                    let heritable_data = &heritable[*cellIndex];
+
+                    let neurons = &mut neuron_chunk[(*cellIndex) - start_index];
+
+                    let mut rng = derive_rng(seed, Stream::Movement, generation, step, *cellIndex);
+
+                    let (coords, kill) = cell::one_step(
+                        neurons,
+                        movement,
+                        heritable_data.get_header().get_oscillator(),
+                        step,
+                        grid,
+                        stepsPerGen,
+                        densityRadius,
+                        sightDistance,
+                        killEnabled,
+                        &mut rng,
+                    );
+                    resChunk[index] = (*cellIndex, coords, kill);
+                }
+            });
+        }
+
+        for (index, cellIndex) in local_living.iter().enumerate() {
+            let movement = &movement[*cellIndex];
+            let heritable_data = &heritable[*cellIndex];
+
+            let neurons = &mut local_neuron[*cellIndex];
+
+            let mut rng = derive_rng(seed, Stream::Movement, generation, step, *cellIndex);
+
+            let (coords, kill) = cell::one_step(
+                neurons,
+                movement,
+                heritable_data.get_header().get_oscillator(),
+                step,
+                grid,
+                stepsPerGen,
+                densityRadius,
+                sightDistance,
+                killEnabled,
+                &mut rng,
+            );
+            localResults[index] = (*cellIndex, coords, kill);
+        }
+    });
+
+    len
+}
+
+fn split_or_get(
+    data: &mut [NeuronData],
+    index: usize,
+) -> (&mut [NeuronData], Option<&mut [NeuronData]>) {
+    if index == data.len() {
+        (data, None)
+    } else {
+        let (fst, lst) = data.split_at_mut(index);
+        (fst, Some(lst))
+    }
+}
+
+pub mod DebugCell {
+    use std::{
+        cell::UnsafeCell,
+        ops::{Deref, DerefMut},
+    };
+
+    #[cfg(debug_assertions)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum RefType {
+        Mutable,
+        Immutable(usize),
+        None,
+    }
+
+    pub struct Ref<'a, T> {
+        reference: &'a T,
+        #[cfg(debug_assertions)]
+        counter: &'a mut RefType,
+    }
+
+    impl<'a, T> Deref for Ref<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            self.reference
+        }
+    }
+
+    impl<'a, T> Drop for Ref<'a, T> {
+        fn drop(&mut self) {
+            #[cfg(debug_assertions)]
+            if let RefType::Immutable(count) = self.counter {
+                if *count == 0 {
+                    panic!("Leaked memory!, invalid");
+                } else if *count == 1 {
+                    *self.counter = RefType::None;
+                    return;
+                } else {
+                    *count -= 1;
+                    return;
+                }
+            }
+            #[cfg(debug_assertions)]
+            panic!("Invalid type");
+        }
+    }
+
+    pub struct RefMut<'a, T> {
+        reference: &'a mut T,
+        #[cfg(debug_assertions)]
+        counter: &'a mut RefType,
+    }
+
+    impl<'a, T> Deref for RefMut<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            self.reference
+        }
+    }
+
+    impl<'a, T> DerefMut for RefMut<'a, T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            self.reference
+        }
+    }
+
+    impl<'a, T> Drop for RefMut<'a, T> {
+        fn drop(&mut self) {
+            #[cfg(debug_assertions)]
+            if let RefType::Mutable = self.counter {
+                *self.counter = RefType::None;
+                return;
+            }
+            #[cfg(debug_assertions)]
+            panic!("Invalid type");
+        }
+    }
+
+    pub struct DebugRefCell<T> {
+        interior: UnsafeCell<T>,
+        #[cfg(debug_assertions)]
+        refType: UnsafeCell<RefType>,
+    }
+
+    impl<T> DebugRefCell<T> {
+        pub fn new(value: T) -> DebugRefCell<T> {
+            #[cfg(debug_assertions)]
+            return DebugRefCell {
+                interior: UnsafeCell::new(value),
+                refType: UnsafeCell::new(RefType::None),
+            };
+            #[cfg(not(debug_assertions))]
+            return DebugRefCell {
+                interior: UnsafeCell::new(value),
+            };
+        }
+
+        pub fn borrow<'a, 'b>(&'b self) -> Ref<'a, T>
+        where
+            'b: 'a,
+        {
+            #[cfg(debug_assertions)]
+            let kind = unsafe { *self.refType.get() };
+            #[cfg(debug_assertions)]
+            match kind {
+                RefType::None => unsafe {
+                    *self.refType.get() = RefType::Immutable(1);
+                    let reference = &*self.interior.get().cast::<T>();
+                    Ref {
+                        reference,
+                        counter: &mut *self.refType.get(),
+                    }
+                },
+                RefType::Immutable(count) => unsafe {
+                    *self.refType.get() = RefType::Immutable(count.checked_add(1).unwrap());
+                    let reference = &*self.interior.get().cast::<T>();
+                    Ref {
+                        reference,
+                        counter: &mut *self.refType.get(),
+                    }
+                },
+                RefType::Mutable => {
+                    panic!("Cannot borrow a immutable reference and a mutable reference at the same time");
+                }
+            }
+            #[cfg(not(debug_assertions))]
+            unsafe {
+                Ref {
+                    reference: &*self.interior.get().cast::<T>(),
+                }
+            }
+        }
+
+        pub fn borrowMut<'a, 'b>(&'b self) -> RefMut<'a, T>
+        where
+            'b: 'a,
+        {
+            #[cfg(debug_assertions)]
+            let kind = unsafe { *self.refType.get() };
+            #[cfg(debug_assertions)]
+            match kind {
+                RefType::None => unsafe {
+                    *self.refType.get() = RefType::Mutable;
+                    let reference = &mut *self.interior.get().cast::<T>();
+                    RefMut {
+                        reference,
+                        counter: &mut *self.refType.get(),
+                    }
+                },
+                RefType::Immutable(_) => panic!(
+                    "Cannot borrow a immutable reference and a mutable reference at the same time"
+                ),
+                RefType::Mutable => panic!("Cannot borrow multiple mutable references"),
+            }
+            #[cfg(not(debug_assertions))]
+            unsafe {
+                RefMut {
+                    reference: &mut *self.interior.get().cast::<T>(),
+                }
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        #[allow(unused)]
+        fn getRefType(&self) -> RefType {
+            unsafe { *self.refType.get() }
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn refDropTest() {
+        let cell = DebugRefCell::new(0 as usize);
+
+        let x = cell.borrow();
+        let y = cell.borrow();
+        let z = cell.borrow();
+
+        assert_eq!(cell.getRefType(), RefType::Immutable(3));
+
+        std::mem::drop(x);
+
+        assert_eq!(cell.getRefType(), RefType::Immutable(2));
+
+        std::mem::drop(z);
+
+        assert_eq!(cell.getRefType(), RefType::Immutable(1));
+
+        std::mem::drop(y);
+
+        assert_eq!(cell.getRefType(), RefType::None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn refMutDropTest() {
+        let cell = DebugRefCell::new(0 as usize);
+
+        let x = cell.borrowMut();
+
+        assert_eq!(cell.getRefType(), RefType::Mutable);
+
+        std::mem::drop(x);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn refImmAliasTest() {
+        let cell = DebugRefCell::new(0 as usize);
+
+        let _x = cell.borrow();
+        let _y = cell.borrow();
+
+        cell.borrowMut();
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn refMutAliasTest() {
+        let cell = DebugRefCell::new(0 as usize);
+
+        let _z = cell.borrowMut();
+
+        let _x = cell.borrow();
+        let _y = cell.borrow();
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn refMultiMut() {
+        let cell = DebugRefCell::new(0 as usize);
+
+        let _z = cell.borrowMut();
+
+        let _x = cell.borrowMut();
+    }
+}
@@ -1,121 +1,145 @@
-#![allow(non_snake_case, non_upper_case_globals, temporary_cstring_as_ptr)]
-#![feature(trace_macros, new_uninit)]
-#![feature(test)]
+#![allow(non_snake_case, non_upper_case_globals)]
 
-use std::slice::{Chunks, ChunksMut};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
 use std::{process::exit, rc::Rc};
 
-extern crate rand;
-
 extern crate custom_dst;
-
-extern crate scoped_threadpool;
-use cell::NeuronData;
-use custom_dst::MaybeUninitDstArray;
-use rand::thread_rng;
-use scoped_threadpool::Pool;
-
-mod windowed;
-use windowed::window::Window;
-
-mod grid;
-use grid::{Grid, GridValueT};
-
-mod population;
-use population::Population;
-
-mod cell;
-
-mod gene;
-use gene::TOTAL_NODE_COUNT;
-
-use crate::cell::HeritableData;
-use crate::gene::Gene;
-use crate::windowed::window::wait;
-use crate::windowed::WindowingStatus;
-mod neuron;
-
-mod bench;
-
-use DebugCell::DebugRefCell;
-
-//Statistics
-static mut neuron_presence: [u32; TOTAL_NODE_COUNT] = [0; TOTAL_NODE_COUNT];
-
-mod config;
-use config::Config as ConfigBase;
-
-type Config = Rc<ConfigBase>;
-type TimeT = usize;
+use custom_dst::DstArray;
+
+extern crate EvolutionSim;
+use EvolutionSim::cell::HeritableData;
+use EvolutionSim::config::{self, Config as ConfigBase};
+use EvolutionSim::dot;
+use EvolutionSim::frame::FrameWriter;
+use EvolutionSim::gene::Gene;
+use EvolutionSim::population::Population;
+use EvolutionSim::recording::Recorder;
+use EvolutionSim::simulation::{GenerationOutcome, GenerationReport, StepReport};
+use EvolutionSim::snapshot;
+use EvolutionSim::stats::{StatsObserver, StatsWriter};
+use EvolutionSim::windowed::window::{wait, wait_for_reset, Window};
+use EvolutionSim::windowed::WindowingStatus;
+use EvolutionSim::DebugCell::DebugRefCell;
+use EvolutionSim::{Config, Simulation, TimeT};
+
+const RUN_CONFIG_PATH: &str = "run_config.toml";
 
 fn main() {
-    println!("The argument file=\"path\" will load the save");
+    let args = ConfigBase::initFromArgs();
+
+    let simulation = match args.get_load_path().map(str::to_string) {
+        Some(path) => {
+            let (mut config, generation, heritable_data) = match load(&path) {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    println!("Error: Failed to load {}: {}", path, err);
+                    exit(1);
+                }
+            };
+            if let Err(err) = config.inherit_run_options(args) {
+                println!("Error: {}", err);
+                exit(config::EXIT_USAGE);
+            }
 
-    let config: Config = Rc::new(ConfigBase::initFromArgs());
+            Simulation::from_snapshot(Rc::new(config), generation, heritable_data)
+        }
+        None => Simulation::new(Rc::new(args)),
+    };
+    let mut simulation = match simulation {
+        Ok(simulation) => simulation,
+        Err(err) => {
+            println!("Error: {}", err);
+            exit(config::EXIT_USAGE);
+        }
+    };
+    let config = simulation.config().clone();
+
+    //Exporting a cell's wiring replaces the run
+    if let Some(path) = config.get_dot_path() {
+        export_dot(
+            &config,
+            simulation.get_generation(),
+            simulation.population(),
+            path,
+        );
+        return;
+    }
 
     println!("{}", config);
 
-    let grid = Rc::new(DebugRefCell::new(Grid::new(
-        config.get_grid_width(),
-        config.get_grid_height(),
-    )));
-
-    let population = Rc::new(DebugRefCell::new(Population::new(
-        &config,
-        &mut grid.borrowMut(),
-    )));
-
-    let scratch = MaybeUninitDstArray::<HeritableData, Gene>::new(
-        config.get_genome_size(),
-        config.get_pop_size(),
-    );
-
-    let mut threadpool = Pool::new(std::thread::available_parallelism().unwrap().get() as u32);
-
-    //Safety: this will be written to before it is ever read from
-    let mut scratch = { unsafe { scratch.assume_init() } };
+    //Lets the run be repeated with --config
+    if let Err(err) = config.write_file(RUN_CONFIG_PATH) {
+        println!("Error: Failed to write {}: {}", RUN_CONFIG_PATH, err);
+    }
 
-    let mut generation = 0;
+    if let Some(path) = config.get_stats_path() {
+        match StatsWriter::create(path) {
+            Ok(writer) => simulation.add_observer(Box::new(StatsObserver::new(writer))),
+            Err(err) => {
+                println!("Error: Failed to create {}: {}", path, err);
+                exit(1);
+            }
+        }
+    }
+    if let Some(dir) = config.get_frames_path() {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            println!("Error: Failed to create {}: {}", dir, err);
+            exit(1);
+        }
+    }
 
     if config.get_is_windowing() {
-        println!("Press R to reset simulation\nPress SPACE to pause and restart simulation\nPress E to print current neuron frequencies\nPress Escape to close window\nPress S to save current generation's genes\nPress C to print config");
+        println!("Press R to reset simulation\nPress SPACE to pause and restart simulation\nPress E to print current neuron and connection frequencies\nPress Escape to close window\nPress S to save current generation's genes\nPress C to print config");
 
         let windowing_status = Rc::new(DebugRefCell::new(WindowingStatus {
             is_paused: false,
             should_reset: false,
+            should_save: false,
+            should_print_connections: false,
         }));
 
         let window = Window::createWindow(&config, &windowing_status, 512, 512)
             .expect("Window failed to be created");
         window.make_current();
 
-        window.render(&config, &population.borrow());
+        window.render(&config, simulation.grid(), simulation.population());
 
         let mut accounted_time = unsafe { glfw::ffi::glfwGetTime() };
 
         let mut outputted = false;
 
-        let mut step = 0;
-
         while !window.shouldClose() {
             window.poll();
             if windowing_status.borrow().should_reset {
-                step = 0;
-                generation = 0;
                 unsafe {
                     accounted_time = glfw::ffi::glfwGetTime();
                     windowing_status.borrowMut().should_reset = false;
                 }
 
-                population
-                    .borrowMut()
-                    .genRandom(&config, &mut grid.borrowMut());
+                simulation.reset();
+            }
+
+            if windowing_status.borrow().should_save {
+                windowing_status.borrowMut().should_save = false;
+
+                save(
+                    &config,
+                    simulation.get_generation(),
+                    simulation.population(),
+                );
+            }
+
+            if windowing_status.borrow().should_print_connections {
+                windowing_status.borrowMut().should_print_connections = false;
+
+                println!("\n{}", simulation.connections());
             }
 
-            if step == 0 && !outputted {
-                window.render(&config, &population.borrow());
+            if simulation.get_step() == 0 && !outputted {
+                window.render(&config, simulation.grid(), simulation.population());
 
-                println!("Generation {}:", generation);
+                println!("Generation {}:", simulation.get_generation());
 
                 outputted = true;
             }
@@ -128,541 +152,152 @@ fn main() {
 
                 accounted_time += 0.016;
 
-                step += 1;
-
-                let size =
-                    computeMovements(&config, &mut threadpool, &mut population.borrowMut(), step);
-                population
-                    .borrowMut()
-                    .resolveMoveQueue(size, &mut grid.borrowMut());
+                print_step(&simulation.step());
 
-                determine_deaths(&config, step, &mut population.borrowMut());
-                population.borrowMut().resolveDead(&mut grid.borrowMut());
-
-                if population.borrow().getLivingIndices().is_empty() {
+                if simulation.is_extinct() {
                     println!("Everyone Died");
-                    loop {
-                        window.poll();
-                        if window.shouldClose() || windowing_status.borrow().should_reset {
-                            unsafe { accounted_time = glfw::ffi::glfwGetTime() };
-                            break;
-                        }
-                    }
+                    simulation.finish_generation();
+                    wait_for_reset(&window, &windowing_status, &mut accounted_time);
                     continue;
                 }
 
-                window.render(&config, &population.borrow());
+                window.render(&config, simulation.grid(), simulation.population());
             }
 
-            if step == config.get_steps_per_gen() {
-                let reproducers = determine_reproducers(&config, &population.borrowMut());
-                if reproducers.is_empty() {
+            if simulation.get_step() == config.get_steps_per_gen() {
+                let report = simulation.finish_generation();
+                if report.outcome == GenerationOutcome::NoReproducers {
                     println!("Failed to produce viable offspring");
-                    loop {
-                        window.poll();
-                        if window.shouldClose() || windowing_status.borrow().should_reset {
-                            unsafe { accounted_time = glfw::ffi::glfwGetTime() };
-                            break;
-                        }
-                    }
+                    wait_for_reset(&window, &windowing_status, &mut accounted_time);
                     continue;
                 }
 
-                println!(
-                    "Dead: {:3}\tReproducing: {:3}\tLiving Non-reproducing: {:3}",
-                    config.get_pop_size() - population.borrow().getLivingIndices().len(),
-                    reproducers.len(),
-                    population.borrow().getLivingIndices().len() - reproducers.len(),
-                );
-
-                grid.borrowMut().reset();
-
-                population.borrowMut().reproduceAsexually(
-                    &mut scratch,
-                    &config,
-                    reproducers,
-                    &mut grid.borrowMut(),
-                );
+                print_generation(&config, &report);
 
                 wait(&window, &windowing_status, &mut accounted_time, 1.0);
 
-                window.render(&config, &population.borrow());
+                window.render(&config, simulation.grid(), simulation.population());
 
-                step = 0;
-                generation += 1;
+                if should_autosave(&config, simulation.get_generation()) {
+                    save(
+                        &config,
+                        simulation.get_generation(),
+                        simulation.population(),
+                    );
+                }
             }
         }
     } else {
-        let steps_per_gen = config.get_steps_per_gen();
+        simulation.add_observer(Box::new(Recorder::new()));
+        simulation.add_observer(Box::new(FrameWriter::new()));
 
-        #[allow(unused_labels)]
-        'gen_loop: loop {
-            println!("Generation {}", generation);
+        loop {
+            println!("Generation {}", simulation.get_generation());
 
-            for step in 0..steps_per_gen {
-                let size =
-                    computeMovements(&config, &mut threadpool, &mut population.borrowMut(), step);
-                population
-                    .borrowMut()
-                    .resolveMoveQueue(size, &mut grid.borrowMut());
-
-                determine_deaths(&config, step, &mut population.borrowMut());
-                population.borrowMut().resolveDead(&mut grid.borrowMut());
+            while !simulation.is_generation_over() {
+                print_step(&simulation.step());
+            }
 
-                if population.borrow().getLivingIndices().is_empty() {
+            let report = simulation.finish_generation();
+            match report.outcome {
+                GenerationOutcome::Reproduced => {}
+                GenerationOutcome::Extinct => {
                     println!("Everyone Died");
                     return;
                 }
+                GenerationOutcome::NoReproducers => {
+                    println!("Failed to produce viable offspring");
+                    exit(1);
+                }
             }
 
-            let reproducers = determine_reproducers(&config, &population.borrowMut());
-            if reproducers.is_empty() {
-                println!("Failed to produce viable offspring");
-                exit(1);
-            }
+            print_generation(&config, &report);
 
-            println!(
-                "Dead: {:3}\tReproducing: {:3}\tLiving Non-reproducing: {:3}",
-                config.get_pop_size() - population.borrow().getLivingIndices().len(),
-                reproducers.len(),
-                population.borrow().getLivingIndices().len() - reproducers.len(),
-            );
-
-            grid.borrowMut().reset();
-
-            population.borrowMut().reproduceAsexually(
-                &mut scratch,
-                &config,
-                reproducers,
-                &mut grid.borrowMut(),
-            );
-            generation += 1;
+            if should_autosave(&config, simulation.get_generation()) {
+                save(
+                    &config,
+                    simulation.get_generation(),
+                    simulation.population(),
+                );
+            }
         }
     }
 }
 
-pub fn computeMovements(
-    config: &Config,
-    threadpool: &mut Pool,
-    pop: &mut Population,
-    step: TimeT,
-) -> usize {
-    let living = pop.getLivingIndices();
-    let len = living.len();
-
-    let parts = {
-        let threads = threadpool.thread_count();
-        let (num, rem) = (
-            living.len() / ((threads + 1) as usize),
-            living.len() % ((threads + 1) as usize),
+fn print_step(report: &StepReport) {
+    if report.killed_by_cells > 0 {
+        println!(
+            "Step {} Killed By Cells: {}",
+            report.step, report.killed_by_cells
         );
-
-        if rem != 0 {
-            num + 1
-        } else {
-            num
-        }
-    };
-
-    let (movement, neuron, heritable, _, results) = pop.get_data_mut();
-
-    //General read-only data
-    let (movement, heritable) = { (&*movement, &heritable.as_shared_slice()) };
-
-    let mut neuron = Some(neuron);
-
-    //Thread IO is chunked for lock-free reading and writing
-    let mut living: Chunks<usize> = living.as_slice().chunks(parts);
-    let mut resChunks: ChunksMut<(usize, (GridValueT, GridValueT))> = results.chunks_mut(parts);
-
-    let gridWidth = config.get_grid_width();
-    let gridHeight = config.get_grid_height();
-    let stepsPerGen = config.get_steps_per_gen();
-
-    threadpool.scoped(|scope| {
-        //Chunked IO for main thread
-        let local_living = living.next().unwrap();
-        let localResults = resChunks.next().unwrap();
-
-        let mut last_included_cell = local_living.last().unwrap();
-        let mut start_index;
-
-        //# Safety
-        //
-        // neuron is declared as a some above, so it must be a some
-        let local_neuron = unsafe {
-            let (first, last) = split_or_get(neuron.unwrap_unchecked(), last_included_cell + 1);
-            //We add two to get the correct index because the first slice doesn't include the index
-            neuron = last;
-            first
-        };
-
-        for living_chunk in living {
-            start_index = last_included_cell + 1;
-            last_included_cell = living_chunk.last().unwrap();
-
-            let neuron_chunk = {
-                //# Safety
-                //
-                //The splitting values will only be equal to the length of the chunk at the end,
-                //and so it will always be Some
-                let (fst, lst) = unsafe {
-                    split_or_get(
-                        neuron.unwrap_unchecked(),
-                        last_included_cell - start_index + 1,
-                    )
-                };
-                neuron = lst;
-                fst
-            };
-            let resChunk = resChunks.next().unwrap();
-
-            scope.execute(move || {
-                let mut rng = thread_rng();
-
-                for (index, cellIndex) in living_chunk.iter().enumerate() {
-                    let movement = &movement[*cellIndex];
-                    let heritable_data = &heritable[*cellIndex];
-
-                    let neurons = &mut neuron_chunk[(*cellIndex) - start_index];
-
-                    let coords = cell::one_step(
-                        neurons,
-                        movement,
-                        heritable_data.get_header().get_oscillator(),
-                        step,
-                        gridWidth,
-                        gridHeight,
-                        stepsPerGen,
-                        &mut rng,
-                    );
-                    resChunk[index] = (*cellIndex, coords);
-                }
-            });
-        }
-
-        let mut rng = thread_rng();
-
-        for (index, cellIndex) in local_living.iter().enumerate() {
-            let movement = &movement[*cellIndex];
-            let heritable_data = &heritable[*cellIndex];
-
-            let neurons = &mut local_neuron[*cellIndex];
-
-            let coords = cell::one_step(
-                neurons,
-                movement,
-                heritable_data.get_header().get_oscillator(),
-                step,
-                gridWidth,
-                gridHeight,
-                stepsPerGen,
-                &mut rng,
-            );
-            localResults[index] = (*cellIndex, coords);
-        }
-    });
-
-    len
-}
-
-fn split_or_get(
-    data: &mut [NeuronData],
-    index: usize,
-) -> (&mut [NeuronData], Option<&mut [NeuronData]>) {
-    if index == data.len() {
-        (data, None)
-    } else {
-        let (fst, lst) = data.split_at_mut(index);
-        (fst, Some(lst))
     }
-}
-pub fn determine_reproducers(config: &Config, pop: &Population) -> Vec<usize> {
-    let mut reproducers = Vec::new();
-    for cellIndex in pop.getLivingIndices() {
-        let cell = pop.getCellMovementData(cellIndex);
-        if cell.getCoords().0 < config.get_grid_width() / 4
-            || cell.getCoords().0 > 3 * config.get_grid_width() / 4
-        {
-            reproducers.push(cellIndex);
-        }
+    if report.killed_by_zones > 0 {
+        println!("Step {} Killed: {}", report.step, report.killed_by_zones);
     }
-
-    reproducers
 }
 
-pub fn determine_deaths(config: &Config, step: TimeT, pop: &mut Population) {
-    if step == config.get_steps_per_gen() / 4 {
-        for index in &pop.getLivingIndices() {
-            let (x, _) = pop.getCellMovementData(*index).getCoords();
-
-            if x < config.get_grid_width() / 4 || x > (3 * config.get_grid_width()) / 4 {
-                pop.addToDeathQueue(*index)
-            }
-        }
-    } else if step == config.get_steps_per_gen() / 2 {
-        for index in &pop.getLivingIndices() {
-            let (x, _) = pop.getCellMovementData(*index).getCoords();
-
-            if x > config.get_grid_width() / 4 && x < (3 * config.get_grid_width()) / 4 {
-                pop.addToDeathQueue(*index)
-            }
-        }
-    } else if step == (3 * config.get_steps_per_gen()) / 4 {
-        for index in &pop.getLivingIndices() {
-            let (x, _) = pop.getCellMovementData(*index).getCoords();
-
-            if x < config.get_grid_width() / 4 || x > (3 * config.get_grid_width()) / 4 {
-                pop.addToDeathQueue(*index)
-            }
-        }
-    }
-
-    if pop.getDeathQueueLen() > 0 {
-        println!("Step {} Killed: {}", step, pop.getDeathQueueLen());
-    }
+fn print_generation(config: &Config, report: &GenerationReport) {
+    println!(
+        "Dead: {:3}\tReproducing: {:3}\tLiving Non-reproducing: {:3}",
+        config.get_pop_size() - report.living,
+        report.stats.reproducers,
+        report.living - report.stats.reproducers,
+    );
+    println!("{}", report.stats.diversity);
 }
 
-pub fn save(config: &Config, generation: TimeT, population: &Population) {}
+pub fn save(config: &Config, generation: TimeT, population: &Population) {
+    let path = format!("generation_{}.sav", generation);
 
-mod DebugCell {
-    use std::{
-        cell::UnsafeCell,
-        ops::{Deref, DerefMut},
-    };
-
-    #[cfg(debug_assertions)]
-    #[derive(Clone, Copy, Debug, PartialEq)]
-    enum RefType {
-        Mutable,
-        Immutable(usize),
-        None,
-    }
-
-    pub struct Ref<'a, T> {
-        reference: &'a T,
-        #[cfg(debug_assertions)]
-        counter: &'a mut RefType,
-    }
-
-    impl<'a, T> Deref for Ref<'a, T> {
-        type Target = T;
-
-        fn deref(&self) -> &Self::Target {
-            self.reference
-        }
-    }
-
-    impl<'a, T> Drop for Ref<'a, T> {
-        fn drop(&mut self) {
-            #[cfg(debug_assertions)]
-            if let RefType::Immutable(count) = self.counter {
-                if *count == 0 {
-                    panic!("Leaked memory!, invalid");
-                } else if *count == 1 {
-                    *self.counter = RefType::None;
-                    return;
-                } else {
-                    *count -= 1;
-                    return;
-                }
-            }
-            #[cfg(debug_assertions)]
-            panic!("Invalid type");
-        }
-    }
-
-    pub struct RefMut<'a, T> {
-        reference: &'a mut T,
-        #[cfg(debug_assertions)]
-        counter: &'a mut RefType,
-    }
-
-    impl<'a, T> Deref for RefMut<'a, T> {
-        type Target = T;
-
-        fn deref(&self) -> &Self::Target {
-            self.reference
-        }
-    }
-
-    impl<'a, T> DerefMut for RefMut<'a, T> {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            self.reference
-        }
-    }
+    let result = File::create(&path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        snapshot::write_snapshot(&mut writer, config, generation, population)?;
+        writer.flush()
+    });
 
-    impl<'a, T> Drop for RefMut<'a, T> {
-        fn drop(&mut self) {
-            #[cfg(debug_assertions)]
-            if let RefType::Mutable = self.counter {
-                *self.counter = RefType::None;
-                return;
-            }
-            #[cfg(debug_assertions)]
-            panic!("Invalid type");
-        }
+    match result {
+        Ok(()) => println!("Saved generation {} to {}", generation, path),
+        Err(err) => println!("Error: Failed to save generation {}: {}", generation, err),
     }
+}
 
-    pub struct DebugRefCell<T> {
-        interior: UnsafeCell<T>,
-        #[cfg(debug_assertions)]
-        refType: UnsafeCell<RefType>,
+fn export_dot(config: &Config, generation: TimeT, population: &Population, path: &str) {
+    let cell = config.get_dot_cell();
+    if cell >= config.get_pop_size() {
+        println!(
+            "Error: Cell {} is out of range for a population of {}",
+            cell,
+            config.get_pop_size()
+        );
+        exit(config::EXIT_USAGE);
     }
 
-    impl<T> DebugRefCell<T> {
-        pub fn new(value: T) -> DebugRefCell<T> {
-            #[cfg(debug_assertions)]
-            return DebugRefCell {
-                interior: UnsafeCell::new(value),
-                refType: UnsafeCell::new(RefType::None),
-            };
-            #[cfg(not(debug_assertions))]
-            return DebugRefCell {
-                interior: UnsafeCell::new(value),
-            };
-        }
-
-        pub fn borrow<'a, 'b>(&'b self) -> Ref<'a, T>
-        where
-            'b: 'a,
-        {
-            #[cfg(debug_assertions)]
-            let kind = unsafe { *self.refType.get() };
-            #[cfg(debug_assertions)]
-            match kind {
-                RefType::None => unsafe {
-                    *self.refType.get() = RefType::Immutable(1);
-                    let reference = &*self.interior.get().cast::<T>();
-                    Ref {
-                        reference,
-                        counter: &mut *self.refType.get(),
-                    }
-                },
-                RefType::Immutable(count) => unsafe {
-                    *self.refType.get() = RefType::Immutable(count.checked_add(1).unwrap());
-                    let reference = &*self.interior.get().cast::<T>();
-                    Ref {
-                        reference,
-                        counter: &mut *self.refType.get(),
-                    }
-                },
-                RefType::Mutable => {
-                    panic!("Cannot borrow a immutable reference and a mutable reference at the same time");
-                }
-            }
-            #[cfg(not(debug_assertions))]
-            unsafe {
-                Ref {
-                    reference: &*self.interior.get().cast::<T>(),
-                }
-            }
-        }
-
-        pub fn borrowMut<'a, 'b>(&'b self) -> RefMut<'a, T>
-        where
-            'b: 'a,
-        {
-            #[cfg(debug_assertions)]
-            let kind = unsafe { *self.refType.get() };
-            #[cfg(debug_assertions)]
-            match kind {
-                RefType::None => unsafe {
-                    *self.refType.get() = RefType::Mutable;
-                    let reference = &mut *self.interior.get().cast::<T>();
-                    RefMut {
-                        reference,
-                        counter: &mut *self.refType.get(),
-                    }
-                },
-                RefType::Immutable(_) => panic!(
-                    "Cannot borrow a immutable reference and a mutable reference at the same time"
-                ),
-                RefType::Mutable => panic!("Cannot borrow multiple mutable references"),
-            }
-            #[cfg(not(debug_assertions))]
-            unsafe {
-                RefMut {
-                    reference: &mut *self.interior.get().cast::<T>(),
-                }
-            }
-        }
+    let result = File::create(path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        dot::write_dot(
+            &mut writer,
+            &format!("generation {} cell {}", generation, cell),
+            population.getCellHeritableData(cell).get_footer(),
+            config.get_inner_nodes(),
+        )?;
+        writer.flush()
+    });
 
-        #[cfg(debug_assertions)]
-        #[allow(unused)]
-        fn getRefType(&self) -> RefType {
-            unsafe { *self.refType.get() }
+    match result {
+        Ok(()) => println!("Wrote the wiring of cell {} to {}", cell, path),
+        Err(err) => {
+            println!("Error: Failed to write {}: {}", path, err);
+            exit(1);
         }
     }
+}
 
-    #[test]
-    #[cfg(debug_assertions)]
-    fn refDropTest() {
-        let cell = DebugRefCell::new(0 as usize);
-
-        let x = cell.borrow();
-        let y = cell.borrow();
-        let z = cell.borrow();
-
-        assert_eq!(cell.getRefType(), RefType::Immutable(3));
-
-        std::mem::drop(x);
-
-        assert_eq!(cell.getRefType(), RefType::Immutable(2));
-
-        std::mem::drop(z);
-
-        assert_eq!(cell.getRefType(), RefType::Immutable(1));
-
-        std::mem::drop(y);
-
-        assert_eq!(cell.getRefType(), RefType::None);
-    }
-
-    #[test]
-    #[cfg(debug_assertions)]
-    fn refMutDropTest() {
-        let cell = DebugRefCell::new(0 as usize);
-
-        let x = cell.borrowMut();
-
-        assert_eq!(cell.getRefType(), RefType::Mutable);
-
-        std::mem::drop(x);
-    }
-
-    #[test]
-    #[should_panic]
-    #[cfg(debug_assertions)]
-    fn refImmAliasTest() {
-        let cell = DebugRefCell::new(0 as usize);
-
-        let _x = cell.borrow();
-        let _y = cell.borrow();
-
-        cell.borrowMut();
-    }
-
-    #[test]
-    #[should_panic]
-    #[cfg(debug_assertions)]
-    fn refMutAliasTest() {
-        let cell = DebugRefCell::new(0 as usize);
-
-        let _z = cell.borrowMut();
-
-        let _x = cell.borrow();
-        let _y = cell.borrow();
-    }
-
-    #[test]
-    #[should_panic]
-    #[cfg(debug_assertions)]
-    fn refMultiMut() {
-        let cell = DebugRefCell::new(0 as usize);
+pub fn load(path: &str) -> io::Result<(ConfigBase, TimeT, DstArray<HeritableData, Gene>)> {
+    let file = File::open(path)?;
 
-        let _z = cell.borrowMut();
+    snapshot::read_snapshot(&mut BufReader::new(file))
+}
 
-        let _x = cell.borrowMut();
-    }
+fn should_autosave(config: &Config, generation: TimeT) -> bool {
+    config.get_save_interval() != 0 && generation.is_multiple_of(config.get_save_interval())
 }
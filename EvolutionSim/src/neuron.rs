@@ -1,30 +1,154 @@
 use std::fmt::Debug;
 
-use crate::gene::{
-    Gene, NodeID, INNER_NODE_COUNT, INPUT_NODE_COUNT, OUTPUT_NODE_COUNT, TOTAL_NODE_COUNT,
-};
+use rand::Rng;
+
+use crate::gene::{Gene, NodeID, INPUT_NODE_COUNT, MAX_INNER_NODE_COUNT, OUTPUT_NODE_COUNT};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Activation {
+    #[default]
+    Relu,
+    Tanh,
+    Sigmoid,
+    Identity,
+    Step,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 5] = [
+        Activation::Relu,
+        Activation::Tanh,
+        Activation::Sigmoid,
+        Activation::Identity,
+        Activation::Step,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Activation> {
+        match name {
+            "relu" => Some(Activation::Relu),
+            "tanh" => Some(Activation::Tanh),
+            "sigmoid" => Some(Activation::Sigmoid),
+            "identity" => Some(Activation::Identity),
+            "step" => Some(Activation::Step),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Activation::Relu => "relu",
+            Activation::Tanh => "tanh",
+            Activation::Sigmoid => "sigmoid",
+            Activation::Identity => "identity",
+            Activation::Step => "step",
+        }
+    }
+
+    //Position in ALL, used when activations are stored
+    pub fn get_index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<Activation> {
+        Activation::ALL.get(index).copied()
+    }
+
+    pub fn get_random<R: Rng>(rng: &mut R) -> Activation {
+        Activation::ALL[rng.gen_range(0..Activation::ALL.len())]
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Activation::Relu => {
+                if value <= 0.0 {
+                    0.0
+                } else {
+                    value
+                }
+            }
+            Activation::Tanh => value.tanh(),
+            Activation::Sigmoid => 1.0 / (1.0 + (-value).exp()),
+            Activation::Identity => value,
+            Activation::Step => {
+                if value > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
 
 pub struct NeuralNet {
-    //Input, Inner, Output
-    neurons: [Neuron; TOTAL_NODE_COUNT],
-    connections: [Connection; NodeID::get_max_connections()],
+    inner_nodes: usize,
+    //Input, Inner, Output, by layout index
+    neurons: Box<[Neuron]>,
+    connections: Box<[Connection]>,
+    //Summed bias genes of every inner and output neuron, the value they start each step at, so a
+    //negative bias works as a threshold the other inputs have to reach
+    biases: Box<[f32]>,
+    //Fraction of an inner neuron's value lost each step, None clears the inner neurons every step
+    decay: Option<f32>,
+    //Inner neuron values of the last step, only kept with a decay
+    previous: Box<[f32]>,
+    inner_activation: Activation,
+    output_activation: Activation,
 }
 
 impl NeuralNet {
-    pub fn new(genome: &[Gene]) -> NeuralNet {
+    pub fn new(genome: &[Gene], inner_nodes: usize) -> NeuralNet {
         let mut net = NeuralNet {
-            neurons: [Neuron { value: 0.0 }; TOTAL_NODE_COUNT],
-            connections: [Connection { weight: 0.0 }; NodeID::get_max_connections()],
+            inner_nodes,
+            neurons: vec![Neuron { value: 0.0 }; NodeID::get_node_count(inner_nodes)]
+                .into_boxed_slice(),
+            connections: vec![Connection { weight: 0.0 }; NodeID::get_max_connections(inner_nodes)]
+                .into_boxed_slice(),
+            biases: vec![0.0; inner_nodes + OUTPUT_NODE_COUNT].into_boxed_slice(),
+            decay: None,
+            previous: Box::new([]),
+            inner_activation: Activation::Relu,
+            output_activation: Activation::Identity,
         };
 
         for gene in genome {
-            net.connections[gene.get_connection_index()].weight += gene.get_weight();
+            if gene.is_bias() {
+                let tail = gene.get_tail_node_id().get_layout_index(inner_nodes);
+                net.biases[tail - INPUT_NODE_COUNT] += gene.get_weight();
+            } else {
+                net.connections[gene.get_connection_index(inner_nodes)].weight += gene.get_weight();
+            }
         }
 
         net
     }
 
+    //Makes the inner neurons recurrent: they keep their value between steps, less the decay, and
+    //inner to inner connections read the values of the last step
+    pub fn with_memory(mut self, decay: Option<f32>) -> NeuralNet {
+        self.decay = decay;
+        self.previous = vec![0.0; decay.map_or(0, |_| self.inner_nodes)].into_boxed_slice();
+        self
+    }
+
+    pub fn with_activations(mut self, inner: Activation, output: Activation) -> NeuralNet {
+        self.inner_activation = inner;
+        self.output_activation = output;
+        self
+    }
+
+    pub fn get_inner_nodes(&self) -> usize {
+        self.inner_nodes
+    }
+
     pub fn prepare_net(&mut self, sensor_values: &[f32]) {
+        for (previous, neuron) in self
+            .previous
+            .iter_mut()
+            .zip(self.neurons[INPUT_NODE_COUNT..].iter())
+        {
+            *previous = neuron.value;
+        }
         self.clear();
 
         self.neurons[NodeID::DistX.get_index()].value = sensor_values[NodeID::DistX.get_index()];
@@ -32,43 +156,92 @@ impl NeuralNet {
         self.neurons[NodeID::Age.get_index()].value = sensor_values[NodeID::Age.get_index()];
         self.neurons[NodeID::Oscillator.get_index()].value =
             sensor_values[NodeID::Oscillator.get_index()];
+        self.neurons[NodeID::PopDensity.get_index()].value =
+            sensor_values[NodeID::PopDensity.get_index()];
+        self.neurons[NodeID::BlockedForward.get_index()].value =
+            sensor_values[NodeID::BlockedForward.get_index()];
+        self.neurons[NodeID::DistToCellForward.get_index()].value =
+            sensor_values[NodeID::DistToCellForward.get_index()];
+        self.neurons[NodeID::DistToWallForward.get_index()].value =
+            sensor_values[NodeID::DistToWallForward.get_index()];
     }
 
     //Sensor Values
     //Index 0: X value
     //Index 1: Y value
     //Index 2: Age
+    //Index 3: Oscillator
+    //Index 4: Population density
+    //Index 5: Blocked forward
+    //Index 6: Distance to cell forward
+    //Index 7: Distance to wall forward
     pub fn feed_forward(&mut self) {
+        let (inner_end, total) = (
+            INPUT_NODE_COUNT + self.inner_nodes,
+            NodeID::get_node_count(self.inner_nodes),
+        );
+
+        if let Some(decay) = self.decay {
+            self.feed_forward_recurrent(decay, inner_end, total);
+            return;
+        }
+
         //Input to Inner
-        for tail in INPUT_NODE_COUNT..(INPUT_NODE_COUNT + INNER_NODE_COUNT) {
+        for tail in INPUT_NODE_COUNT..inner_end {
             for head in 0..INPUT_NODE_COUNT {
                 self.neurons[tail].value +=
                     self.neurons[head].value * self.get_connection(head, tail).weight
             }
-            self.neurons[tail].value = activation(self.neurons[tail].value);
+            self.neurons[tail].value = self.inner_activation.apply(self.neurons[tail].value);
         }
 
         //Input to Output
-        for tail in (INPUT_NODE_COUNT + INNER_NODE_COUNT)..TOTAL_NODE_COUNT {
+        for tail in inner_end..total {
             for head in 0..INPUT_NODE_COUNT {
                 self.neurons[tail].value +=
                     self.neurons[head].value * self.get_connection(head, tail).weight
             }
         }
 
-        //Inner to Inner
-        for tail in INPUT_NODE_COUNT..(INPUT_NODE_COUNT + INNER_NODE_COUNT) {
-            for head in INPUT_NODE_COUNT..(INPUT_NODE_COUNT + INNER_NODE_COUNT) {
+        //Inner to Inner, activated once more as a stage of its own
+        for tail in INPUT_NODE_COUNT..inner_end {
+            for head in INPUT_NODE_COUNT..inner_end {
                 self.neurons[tail].value +=
                     self.neurons[head].value * self.get_connection(head, tail).weight
             }
 
-            self.neurons[tail].value = activation(self.neurons[tail].value);
+            self.neurons[tail].value = self.inner_activation.apply(self.neurons[tail].value);
         }
 
         //Inner to Output
-        for tail in (INPUT_NODE_COUNT + INNER_NODE_COUNT)..TOTAL_NODE_COUNT {
-            for head in INPUT_NODE_COUNT..(INPUT_NODE_COUNT + INNER_NODE_COUNT) {
+        for tail in inner_end..total {
+            for head in INPUT_NODE_COUNT..inner_end {
+                self.neurons[tail].value +=
+                    self.neurons[head].value * self.get_connection(head, tail).weight
+            }
+        }
+    }
+
+    //Inner neurons are all computed from the inputs and last step's inner values, so the order they
+    //are evaluated in doesn't matter
+    fn feed_forward_recurrent(&mut self, decay: f32, inner_end: usize, total: usize) {
+        for tail in INPUT_NODE_COUNT..inner_end {
+            let mut value = self.biases[tail - INPUT_NODE_COUNT]
+                + self.previous[tail - INPUT_NODE_COUNT] * (1.0 - decay);
+
+            for head in 0..INPUT_NODE_COUNT {
+                value += self.neurons[head].value * self.get_connection(head, tail).weight;
+            }
+            for head in INPUT_NODE_COUNT..inner_end {
+                value +=
+                    self.previous[head - INPUT_NODE_COUNT] * self.get_connection(head, tail).weight;
+            }
+
+            self.neurons[tail].value = self.inner_activation.apply(value);
+        }
+
+        for tail in inner_end..total {
+            for head in 0..inner_end {
                 self.neurons[tail].value +=
                     self.neurons[head].value * self.get_connection(head, tail).weight
             }
@@ -78,55 +251,117 @@ impl NeuralNet {
     pub fn get_outputs(&self) -> [f32; OUTPUT_NODE_COUNT] {
         let mut outputs = [0.0; OUTPUT_NODE_COUNT];
 
-        for neuron in (INPUT_NODE_COUNT + INNER_NODE_COUNT)..(TOTAL_NODE_COUNT) {
-            outputs[neuron - INPUT_NODE_COUNT - INNER_NODE_COUNT] = self.neurons[neuron].value;
+        let inner_end = INPUT_NODE_COUNT + self.inner_nodes;
+        for (output, neuron) in self.neurons[inner_end..].iter().enumerate() {
+            outputs[output] = self.output_activation.apply(neuron.value);
         }
 
         outputs
     }
 
+    //Inputs go to 0 and everything else to its bias
     pub fn clear(&mut self) {
-        for index in 0..self.neurons.len() {
+        for index in 0..INPUT_NODE_COUNT {
             self.neurons[index].value = 0.0;
         }
+        for (neuron, bias) in self.neurons[INPUT_NODE_COUNT..]
+            .iter_mut()
+            .zip(self.biases.iter())
+        {
+            neuron.value = *bias;
+        }
+    }
+
+    //Summed weight of every bias gene of an inner or output node
+    pub fn get_bias(&self, node: NodeID) -> f32 {
+        self.biases[node.get_layout_index(self.inner_nodes) - INPUT_NODE_COUNT]
+    }
+
+    //Summed weight of every gene connecting head to tail
+    pub fn get_weight(&self, head: NodeID, tail: NodeID) -> f32 {
+        self.connections[NeuralNet::get_connection_index(head, tail, self.inner_nodes)].weight
     }
 
+    //head and tail are layout indices
     fn get_connection(&self, head: usize, tail: usize) -> Connection {
-        self.connections
-            [NeuralNet::get_connection_index(NodeID::from_index(head), NodeID::from_index(tail))]
+        self.connections[NeuralNet::get_connection_index(
+            NodeID::from_layout_index(head, self.inner_nodes),
+            NodeID::from_layout_index(tail, self.inner_nodes),
+            self.inner_nodes,
+        )]
     }
 
-    pub const fn get_connection_index(head: NodeID, tail: NodeID) -> usize {
+    pub const fn get_connection_index(head: NodeID, tail: NodeID, inner_nodes: usize) -> usize {
         if head.is_input() {
             if tail.is_inner() {
-                tail.get_inner_index() + head.get_input_index() * INPUT_NODE_COUNT
+                tail.get_inner_index() + head.get_input_index() * inner_nodes
             } else {
                 tail.get_output_index()
-                    + head.get_input_index() * INNER_NODE_COUNT
-                    + (INPUT_NODE_COUNT * INNER_NODE_COUNT)
+                    + head.get_input_index() * OUTPUT_NODE_COUNT
+                    + (INPUT_NODE_COUNT * inner_nodes)
             }
         } else if tail.is_inner() {
             tail.get_inner_index()
-                + head.get_inner_index() * INNER_NODE_COUNT
-                + (INPUT_NODE_COUNT * INNER_NODE_COUNT + INPUT_NODE_COUNT * OUTPUT_NODE_COUNT)
+                + head.get_inner_index() * inner_nodes
+                + (INPUT_NODE_COUNT * inner_nodes + INPUT_NODE_COUNT * OUTPUT_NODE_COUNT)
         } else {
             tail.get_output_index()
                 + head.get_inner_index() * OUTPUT_NODE_COUNT
-                + (INPUT_NODE_COUNT * INNER_NODE_COUNT
+                + (INPUT_NODE_COUNT * inner_nodes
                     + INPUT_NODE_COUNT * OUTPUT_NODE_COUNT
-                    + INNER_NODE_COUNT * INNER_NODE_COUNT)
+                    + inner_nodes * inner_nodes)
         }
     }
-}
 
-fn activation(value: f32) -> f32 {
-    if value <= 0.0 {
-        0.0
-    } else {
-        value
+    //Whether get_connection_index gives every head and tail pair its own index below
+    //get_max_connections, with as many pairs as there are connections
+    pub const fn is_connection_layout_bijective(inner_nodes: usize) -> bool {
+        let max_connections = NodeID::get_max_connections(inner_nodes);
+        let mut used = [false; NodeID::get_max_connections(MAX_INNER_NODE_COUNT)];
+        let mut pairs = 0;
+
+        let mut head = 0;
+        while head < INPUT_NODE_COUNT + inner_nodes {
+            let head_id = if head < INPUT_NODE_COUNT {
+                NodeID::as_input(head)
+            } else {
+                NodeID::as_inner(head - INPUT_NODE_COUNT)
+            };
+
+            let mut tail = 0;
+            while tail < inner_nodes + OUTPUT_NODE_COUNT {
+                let tail_id = if tail < inner_nodes {
+                    NodeID::as_inner(tail)
+                } else {
+                    NodeID::as_output(tail - inner_nodes)
+                };
+
+                let index = NeuralNet::get_connection_index(head_id, tail_id, inner_nodes);
+                if index >= max_connections || used[index] {
+                    return false;
+                }
+                used[index] = true;
+                pairs += 1;
+
+                tail += 1;
+            }
+            head += 1;
+        }
+
+        pairs == max_connections
     }
 }
 
+//Checked for every inner node count, so changing the node counts or the layout can't silently make
+//genes share weights
+const _: () = {
+    let mut inner_nodes = 0;
+    while inner_nodes <= MAX_INNER_NODE_COUNT {
+        assert!(NeuralNet::is_connection_layout_bijective(inner_nodes));
+        inner_nodes += 1;
+    }
+};
+
 #[derive(Debug, Copy, Clone)]
 struct Neuron {
     value: f32,
@@ -140,15 +375,148 @@ struct Connection {
 impl Debug for NeuralNet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Neurons: [")?;
-        for neuron in 0..TOTAL_NODE_COUNT {
-            writeln!(
-                f,
-                "\t  {:10}: {}",
-                NodeID::from_index(neuron),
-                self.neurons[neuron].value
-            )?;
+        for (node, neuron) in NodeID::get_nodes(self.inner_nodes).zip(self.neurons.iter()) {
+            writeln!(f, "\t  {:10}: {}", node, neuron.value)?;
         }
         writeln!(f, "]")?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Activation, NeuralNet};
+    use crate::gene::{Gene, NodeID, INPUT_NODE_COUNT, MAX_INNER_NODE_COUNT};
+
+    //Returns MoveNorth and MoveEast
+    fn step(net: &mut NeuralNet, age: f32) -> (f32, f32) {
+        let mut sensors = [0.0; INPUT_NODE_COUNT];
+        sensors[NodeID::Age.get_index()] = age;
+
+        net.prepare_net(&sensors);
+        net.feed_forward();
+
+        let outputs = net.get_outputs();
+        (
+            outputs[NodeID::MoveNorth.get_output_index()],
+            outputs[NodeID::MoveEast.get_output_index()],
+        )
+    }
+
+    #[test]
+    fn recurrent_inner_nodes() {
+        //A weight of 8191 is 1.0
+        let genome = [
+            Gene::from_parts(NodeID::Age, NodeID::as_inner(0), 16382, 2),
+            Gene::from_parts(NodeID::as_inner(0), NodeID::as_inner(1), 8191, 2),
+            Gene::from_parts(NodeID::as_inner(0), NodeID::MoveNorth, 8191, 2),
+            Gene::from_parts(NodeID::as_inner(1), NodeID::MoveEast, 8191, 2),
+        ];
+
+        //Inner to inner connections read this step's values and nothing is kept
+        let mut net = NeuralNet::new(&genome, 2);
+        assert_eq!(step(&mut net, 1.0), (2.0, 2.0));
+        assert_eq!(step(&mut net, 0.0), (0.0, 0.0));
+
+        let mut net = NeuralNet::new(&genome, 2).with_memory(Some(0.25));
+        assert_eq!(step(&mut net, 1.0), (2.0, 0.0));
+        assert_eq!(step(&mut net, 0.0), (1.5, 2.0));
+        assert_eq!(step(&mut net, 0.0), (1.125, 3.0));
+    }
+
+    #[test]
+    fn connection_layout() {
+        for inner_nodes in 0..=MAX_INNER_NODE_COUNT {
+            let nodes: Vec<NodeID> = NodeID::get_nodes(inner_nodes).collect();
+            let mut indices: Vec<usize> = nodes
+                .iter()
+                .filter(|head| !head.is_output())
+                .flat_map(|head| {
+                    nodes
+                        .iter()
+                        .filter(|tail| !tail.is_input())
+                        .map(move |tail| NeuralNet::get_connection_index(*head, *tail, inner_nodes))
+                })
+                .collect();
+
+            indices.sort_unstable();
+            assert_eq!(
+                indices,
+                (0..NodeID::get_max_connections(inner_nodes)).collect::<Vec<_>>()
+            );
+            assert!(NeuralNet::is_connection_layout_bijective(inner_nodes));
+        }
+
+        //Each gene's weight ends up on its own connection
+        let genome = [
+            Gene::from_parts(NodeID::DistToWallForward, NodeID::as_inner(1), 8191, 2),
+            Gene::from_parts(NodeID::DistY, NodeID::MoveNorth, 16382, 2),
+            Gene::from_parts(NodeID::DistX, NodeID::MoveEast, -8191, 2),
+        ];
+        let net = NeuralNet::new(&genome, 2);
+        assert_eq!(
+            net.get_weight(NodeID::DistToWallForward, NodeID::as_inner(1)),
+            1.0
+        );
+        assert_eq!(net.get_weight(NodeID::DistY, NodeID::MoveNorth), 2.0);
+        assert_eq!(net.get_weight(NodeID::DistX, NodeID::MoveEast), -1.0);
+        assert_eq!(net.get_weight(NodeID::DistX, NodeID::MoveNorth), 0.0);
+    }
+
+    #[test]
+    fn biases() {
+        let genome = [
+            Gene::from_parts(NodeID::Bias, NodeID::MoveEast, 8191, 2),
+            Gene::from_parts(NodeID::Age, NodeID::MoveEast, -8191, 2),
+            //A negative bias is a threshold Age has to pass before Inner1 fires
+            Gene::from_parts(NodeID::Bias, NodeID::as_inner(0), -4095, 2),
+            Gene::from_parts(NodeID::Bias, NodeID::as_inner(0), -4096, 2),
+            Gene::from_parts(NodeID::Age, NodeID::as_inner(0), 8191, 2),
+            Gene::from_parts(NodeID::as_inner(0), NodeID::MoveNorth, 8191, 2),
+        ];
+
+        let mut net = NeuralNet::new(&genome, 2);
+        assert_eq!(net.get_bias(NodeID::MoveEast), 1.0);
+        assert_eq!(net.get_weight(NodeID::Age, NodeID::MoveEast), -1.0);
+        assert_eq!(step(&mut net, 0.0), (0.0, 1.0));
+        assert_eq!(step(&mut net, 0.5), (0.0, 0.5));
+        assert_eq!(step(&mut net, 1.0), (0.0, 0.0));
+
+        let mut net = NeuralNet::new(&genome, 2).with_memory(Some(1.0));
+        assert_eq!(step(&mut net, 1.5), (0.5, -0.5));
+    }
+
+    #[test]
+    fn activations() {
+        let genome = [
+            Gene::from_parts(NodeID::Age, NodeID::as_inner(0), -8191, 2),
+            Gene::from_parts(NodeID::as_inner(0), NodeID::MoveNorth, 8191, 2),
+            Gene::from_parts(NodeID::Age, NodeID::MoveEast, 16382, 2),
+        ];
+
+        //Relu inner and identity output by default
+        let mut net = NeuralNet::new(&genome, 1);
+        assert_eq!(step(&mut net, 1.0), (0.0, 2.0));
+
+        let mut net =
+            NeuralNet::new(&genome, 1).with_activations(Activation::Identity, Activation::Step);
+        assert_eq!(step(&mut net, 1.0), (0.0, 1.0));
+
+        let mut net =
+            NeuralNet::new(&genome, 1).with_activations(Activation::Tanh, Activation::Identity);
+        //Inner neurons are activated after the input stage and again after the inner stage
+        assert_eq!(step(&mut net, 1.0), ((-1.0f32).tanh().tanh(), 2.0));
+
+        assert_eq!(Activation::Sigmoid.apply(0.0), 0.5);
+        for activation in Activation::ALL {
+            assert_eq!(
+                Activation::from_name(activation.get_name()),
+                Some(activation)
+            );
+            assert_eq!(
+                Activation::from_index(activation.get_index()),
+                Some(activation)
+            );
+        }
+    }
+}
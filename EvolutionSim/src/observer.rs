@@ -0,0 +1,157 @@
+use crate::{
+    grid::{Grid, GridValueT},
+    population::Population,
+    simulation::{GenerationReport, StepReport},
+    Config, TimeT,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeathCause {
+    //Attacked by the cell with this index
+    Cell(usize),
+    //Inside the zone of the kill schedule event with this index
+    Zone(usize),
+}
+
+//What an observer can look at when it is notified
+pub struct Context<'a> {
+    pub config: &'a Config,
+    pub grid: &'a Grid,
+    pub population: &'a Population,
+    pub generation: TimeT,
+    //Index of the step within its generation, for generation_end the amount of steps taken
+    pub step: TimeT,
+}
+
+//Callbacks for everything that happens in a simulation, every one of them does nothing by default
+//
+//Cell indices are indices into the population at the time of the callback, except for the parents
+//given to reproduced, which index the generation that just ended
+pub trait Observer {
+    fn step_start(&mut self, _context: &Context) {}
+
+    //Only called for cells that changed tile
+    fn cell_moved(
+        &mut self,
+        _cell: usize,
+        _from: (GridValueT, GridValueT),
+        _to: (GridValueT, GridValueT),
+    ) {
+    }
+
+    fn cell_died(&mut self, _cell: usize, _cause: DeathCause) {}
+
+    fn step_end(&mut self, _context: &Context, _report: &StepReport) {}
+
+    //Called before the next generation is bred, even if it cannot be
+    fn generation_end(&mut self, _context: &Context, _report: &GenerationReport) {}
+
+    //Called once for each parent of a child, after the whole next generation has been bred
+    fn reproduced(&mut self, _parent: usize, _child: usize) {}
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{Context, DeathCause, Observer};
+    use crate::{
+        config::Config,
+        grid::GridValueT,
+        kill_schedule::KillSchedule,
+        selection,
+        simulation::{GenerationOutcome, GenerationReport, StepReport},
+        Simulation,
+    };
+
+    #[derive(Default)]
+    struct Counts {
+        steps: usize,
+        moves: usize,
+        zone_deaths: usize,
+        cell_deaths: usize,
+        reported_deaths: usize,
+        generations: usize,
+        children: Vec<usize>,
+    }
+
+    struct Counter(Rc<RefCell<Counts>>);
+
+    impl Observer for Counter {
+        fn step_start(&mut self, context: &Context) {
+            assert_eq!(context.step, self.0.borrow().steps);
+        }
+
+        fn cell_moved(
+            &mut self,
+            _cell: usize,
+            from: (GridValueT, GridValueT),
+            to: (GridValueT, GridValueT),
+        ) {
+            assert_ne!(from, to);
+            self.0.borrow_mut().moves += 1;
+        }
+
+        fn cell_died(&mut self, _cell: usize, cause: DeathCause) {
+            match cause {
+                DeathCause::Cell(_) => self.0.borrow_mut().cell_deaths += 1,
+                DeathCause::Zone(event) => {
+                    assert_eq!(event, 0);
+                    self.0.borrow_mut().zone_deaths += 1;
+                }
+            }
+        }
+
+        fn step_end(&mut self, _context: &Context, report: &StepReport) {
+            let mut counts = self.0.borrow_mut();
+            counts.steps += 1;
+            counts.reported_deaths += report.killed_by_cells + report.killed_by_zones;
+        }
+
+        fn generation_end(&mut self, context: &Context, report: &GenerationReport) {
+            let mut counts = self.0.borrow_mut();
+            assert_eq!(context.generation, 0);
+            assert_eq!(report.stats.kill_deaths, vec![counts.zone_deaths]);
+            assert_eq!(report.stats.kill_forward_deaths, counts.cell_deaths);
+            counts.generations += 1;
+        }
+
+        fn reproduced(&mut self, _parent: usize, child: usize) {
+            self.0.borrow_mut().children.push(child);
+        }
+    }
+
+    #[test]
+    fn notifies_observers() {
+        let mut config = Config::new(100, 8, 24, 24, 0.1, 20, false).unwrap();
+        config.set_seed(3);
+        config.set_kill_enabled(true);
+        config.set_selection(selection::parse_criterion("edge(east, 0.5)").unwrap());
+        config.set_kill_schedule(KillSchedule::parse("10 circle(0.5, 0.5, 0.25)").unwrap());
+
+        let counts = Rc::new(RefCell::new(Counts::default()));
+        let mut simulation = Simulation::new(Rc::new(config)).unwrap();
+        simulation.add_observer(Box::new(Counter(counts.clone())));
+
+        let report = simulation.run_generation();
+        assert_eq!(report.outcome, GenerationOutcome::Reproduced);
+
+        let counts = counts.borrow();
+        assert_eq!((counts.steps, counts.generations), (20, 1));
+        assert!(counts.moves > 0);
+        assert!(counts.zone_deaths > 0);
+        assert_eq!(
+            counts.reported_deaths,
+            counts.zone_deaths + counts.cell_deaths
+        );
+        assert_eq!(report.living, 100 - counts.reported_deaths);
+
+        //Asexual children have one parent each
+        assert_eq!(counts.children.len(), 100);
+        assert!(counts
+            .children
+            .iter()
+            .enumerate()
+            .all(|(index, child)| index == *child));
+    }
+}
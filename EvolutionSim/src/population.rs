@@ -1,5 +1,5 @@
 use custom_dst::{DstArray, DstData, DstSliceMut, MaybeUninitDstArray};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use crate::{
     cell::{
@@ -12,6 +12,59 @@ use crate::{
     Config,
 };
 
+//Cell index, requested position, and whether it attacks the tile in front of it
+pub type MoveRequest = (usize, (GridValueT, GridValueT), bool);
+
+//Cell index, position before the move, and position after it
+pub type CellMove = (usize, (GridValueT, GridValueT), (GridValueT, GridValueT));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reproduction {
+    Asexual,
+    Sexual,
+}
+
+impl Reproduction {
+    pub fn from_name(name: &str) -> Option<Reproduction> {
+        match name {
+            "asexual" => Some(Reproduction::Asexual),
+            "sexual" => Some(Reproduction::Sexual),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Reproduction::Asexual => "asexual",
+            Reproduction::Sexual => "sexual",
+        }
+    }
+}
+
+//How the second parent is chosen in sexual reproduction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pairing {
+    Random,
+    Nearest,
+}
+
+impl Pairing {
+    pub fn from_name(name: &str) -> Option<Pairing> {
+        match name {
+            "random" => Some(Pairing::Random),
+            "nearest" => Some(Pairing::Nearest),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Pairing::Random => "random",
+            Pairing::Nearest => "nearest",
+        }
+    }
+}
+
 pub struct Population {
     size: usize,
     movement_data: Box<[MovementData]>,
@@ -20,11 +73,11 @@ pub struct Population {
     misc_data: Box<[MiscData]>,
     deathQueue: Box<[usize]>,
     deathSize: usize,
-    moveQueue: Box<[(usize, (GridValueT, GridValueT))]>,
+    moveQueue: Box<[MoveRequest]>,
 }
 
 impl Population {
-    pub fn new(config: &Config, grid: &mut Grid) -> Population {
+    pub fn new<R: Rng>(config: &Config, grid: &mut Grid, rng: &mut R) -> Population {
         let mut movement_data = std::boxed::Box::new_uninit_slice(config.get_pop_size());
         let mut neuron_data = std::boxed::Box::new_uninit_slice(config.get_pop_size());
         let mut misc_data = Box::new_uninit_slice(config.get_pop_size());
@@ -32,29 +85,31 @@ impl Population {
         let mut other_data =
             MaybeUninitDstArray::new(config.get_genome_size(), config.get_pop_size());
 
-        let mut rng = thread_rng();
-
         for index in 0..config.get_pop_size() {
             let movement = {
-                let (x, y) = grid.find_random_unoccupied();
+                let (x, y) = grid.find_random_unoccupied(rng);
 
-                MovementData::new(x, y, Direction::get_random(&mut rng))
+                grid.set_occupant(x, y, Some(index));
+
+                MovementData::new(x, y, Direction::get_random(rng))
             };
             movement_data[index].write(movement);
 
             unsafe {
-                write_random_other_init(
+                let header = write_random_other_init(
                     &mut other_data,
                     index,
-                    &mut rng,
+                    rng,
                     config.get_genome_size(),
                     config.get_steps_per_gen(),
+                    config.get_inner_nodes(),
+                    config.get_inherit_activation(),
                 );
 
                 //SAFETY Safe because we initialized heritable data above
                 let genome = &*other_data.get_footer_ptr(index);
 
-                neuron_data[index].write(NeuronData::new(NeuralNet::new(genome)));
+                neuron_data[index].write(new_neuron_data(&header, genome, config));
 
                 misc_data[index].write(MiscData::new(genome));
             }
@@ -85,78 +140,273 @@ impl Population {
         }
     }
 
-    pub fn genRandom(&mut self, config: &Config, grid: &mut Grid) {
-        let mut rng = thread_rng();
+    pub fn from_heritable<R: Rng>(
+        config: &Config,
+        grid: &mut Grid,
+        heritable_data: DstArray<HeritableData, Gene>,
+        rng: &mut R,
+    ) -> Population {
+        let mut movement_data = Vec::with_capacity(config.get_pop_size());
+        let mut neuron_data = Vec::with_capacity(config.get_pop_size());
+        let mut misc_data = Vec::with_capacity(config.get_pop_size());
+
+        for index in 0..config.get_pop_size() {
+            let (x, y) = grid.find_random_unoccupied(rng);
+
+            grid.set_occupant(x, y, Some(index));
+
+            movement_data.push(MovementData::new(x, y, Direction::get_random(rng)));
+
+            let cell = heritable_data.get_arr_element(index);
+            let genome = cell.get_footer();
+
+            neuron_data.push(new_neuron_data(cell.get_header(), genome, config));
+            misc_data.push(MiscData::new(genome));
+        }
+
+        Population {
+            size: config.get_pop_size(),
+            movement_data: movement_data.into_boxed_slice(),
+            neuron_data: neuron_data.into_boxed_slice(),
+            heritable_data,
+            deathQueue: unsafe {
+                std::boxed::Box::new_zeroed_slice(config.get_pop_size()).assume_init()
+            },
+            deathSize: 0,
+            moveQueue: unsafe {
+                std::boxed::Box::new_zeroed_slice(config.get_pop_size()).assume_init()
+            },
+            misc_data: misc_data.into_boxed_slice(),
+        }
+    }
 
+    pub fn genRandom<R: Rng>(&mut self, config: &Config, grid: &mut Grid, rng: &mut R) {
         let heritable = &mut self.heritable_data.get_mut_slice(0, self.size);
 
         for index in 0..config.get_pop_size() {
             self.movement_data[index] = {
-                let (x, y) = grid.find_random_unoccupied();
+                let (x, y) = grid.find_random_unoccupied(rng);
 
                 grid.set_occupant(x, y, Some(index));
 
                 MovementData {
                     x,
                     y,
-                    lastMoveDir: Direction::get_random(&mut rng),
+                    lastMoveDir: Direction::get_random(rng),
                 }
             };
 
-            gen_random_other(&mut heritable[index], &mut rng, config.get_steps_per_gen());
+            gen_random_other(
+                &mut heritable[index],
+                rng,
+                config.get_steps_per_gen(),
+                config.get_inner_nodes(),
+                config.get_inherit_activation(),
+            );
 
-            self.neuron_data[index] =
-                NeuronData::new(NeuralNet::new(heritable[index].get_footer()));
+            self.neuron_data[index] = new_neuron_data(
+                heritable[index].get_header(),
+                heritable[index].get_footer(),
+                config,
+            );
 
             self.misc_data[index] = MiscData::new(heritable[index].get_footer());
         }
     }
 
-    pub fn reproduceAsexually(
+    //Returns the parents of every child, asexual offspring have the same parent twice
+    pub fn reproduce<R: Rng>(
         &mut self,
         scratch: &mut DstArray<HeritableData, Gene>,
         config: &Config,
         reproducingCells: Vec<usize>,
         grid: &mut Grid,
-    ) {
+        rng: &mut R,
+    ) -> Vec<(usize, usize)> {
+        match config.get_reproduction() {
+            Reproduction::Asexual => {
+                self.reproduceAsexually(scratch, config, reproducingCells, grid, rng)
+            }
+            Reproduction::Sexual => {
+                self.reproduceSexually(scratch, config, reproducingCells, grid, rng)
+            }
+        }
+    }
+
+    pub fn reproduceSexually<R: Rng>(
+        &mut self,
+        scratch: &mut DstArray<HeritableData, Gene>,
+        config: &Config,
+        reproducingCells: Vec<usize>,
+        grid: &mut Grid,
+        rng: &mut R,
+    ) -> Vec<(usize, usize)> {
+        //Partners are found before any movement data is overwritten by the offspring
+        let nearest = match config.get_pairing() {
+            Pairing::Random => Vec::new(),
+            Pairing::Nearest => self.findNearestPartners(&reproducingCells),
+        };
+
         //Prevents alloc in hot loop
         //Old heritable data is now in scratch
         self.heritable_data.swap(scratch);
 
         let mutationRate = config.get_mutation_rate();
         let stepsPerGen = config.get_steps_per_gen();
+        let innerNodes = config.get_inner_nodes();
+        let inheritActivation = config.get_inherit_activation();
+
+        let mut new_heritable_data = self.heritable_data.get_mut_slice(0, config.get_pop_size());
+        let mut parents = Vec::with_capacity(config.get_pop_size());
 
-        let mut rng = rand::thread_rng();
+        for index in 0..config.get_pop_size() {
+            let first = rng.gen_range(0..reproducingCells.len());
+            let second = match config.get_pairing() {
+                Pairing::Nearest => nearest[first],
+                //A lone reproducer has to pair with itself
+                Pairing::Random if reproducingCells.len() == 1 => first,
+                Pairing::Random => {
+                    //Picks from every other reproducer
+                    let other = rng.gen_range(0..reproducingCells.len() - 1);
+                    if other >= first {
+                        other + 1
+                    } else {
+                        other
+                    }
+                }
+            };
+
+            self.movement_data[index] = {
+                let (x, y) = grid.find_random_unoccupied(rng);
+
+                grid.set_occupant(x, y, Some(index));
+
+                MovementData {
+                    x,
+                    y,
+                    lastMoveDir: Direction::get_random(rng),
+                }
+            };
+
+            parents.push((reproducingCells[first], reproducingCells[second]));
+
+            cell::sexuallyReproduce(
+                &scratch[reproducingCells[first]],
+                &scratch[reproducingCells[second]],
+                &mut new_heritable_data[index],
+                stepsPerGen,
+                mutationRate,
+                innerNodes,
+                rng,
+            );
+            if inheritActivation {
+                let child = new_heritable_data[index].get_header_mut();
+                child.cross_activations(
+                    scratch[reproducingCells[first]].get_header(),
+                    scratch[reproducingCells[second]].get_header(),
+                    rng,
+                );
+                child.mutate_activations(mutationRate, rng);
+            }
+
+            self.neuron_data[index] = new_neuron_data(
+                new_heritable_data[index].get_header(),
+                new_heritable_data[index].get_footer(),
+                config,
+            );
+
+            self.misc_data[index] = MiscData::new(new_heritable_data[index].get_footer());
+        }
+
+        parents
+    }
+
+    //For each reproducer, the position in reproducingCells of the closest other reproducer
+    fn findNearestPartners(&self, reproducingCells: &[usize]) -> Vec<usize> {
+        let coords: Vec<(isize, isize)> = reproducingCells
+            .iter()
+            .map(|&cellIndex| {
+                let movement = &self.movement_data[cellIndex];
+                (movement.x as isize, movement.y as isize)
+            })
+            .collect();
+
+        (0..coords.len())
+            .map(|index| {
+                let (x, y) = coords[index];
+
+                (0..coords.len())
+                    .filter(|&other| other != index)
+                    .min_by_key(|&other| {
+                        let (dx, dy) = (coords[other].0 - x, coords[other].1 - y);
+                        dx * dx + dy * dy
+                    })
+                    .unwrap_or(index)
+            })
+            .collect()
+    }
+
+    pub fn reproduceAsexually<R: Rng>(
+        &mut self,
+        scratch: &mut DstArray<HeritableData, Gene>,
+        config: &Config,
+        reproducingCells: Vec<usize>,
+        grid: &mut Grid,
+        rng: &mut R,
+    ) -> Vec<(usize, usize)> {
+        //Prevents alloc in hot loop
+        //Old heritable data is now in scratch
+        self.heritable_data.swap(scratch);
+
+        let mutationRate = config.get_mutation_rate();
+        let stepsPerGen = config.get_steps_per_gen();
+        let innerNodes = config.get_inner_nodes();
+        let inheritActivation = config.get_inherit_activation();
 
         let mut new_heritable_data = self.heritable_data.get_mut_slice(0, config.get_pop_size());
+        let mut parents = Vec::with_capacity(config.get_pop_size());
 
         for index in 0..config.get_pop_size() {
             let selectedCell = reproducingCells[rng.gen_range(0..reproducingCells.len())];
 
             self.movement_data[index] = {
-                let (x, y) = grid.find_random_unoccupied();
+                let (x, y) = grid.find_random_unoccupied(rng);
 
                 grid.set_occupant(x, y, Some(index));
 
                 MovementData {
                     x,
                     y,
-                    lastMoveDir: Direction::get_random(&mut rng),
+                    lastMoveDir: Direction::get_random(rng),
                 }
             };
 
+            parents.push((selectedCell, selectedCell));
+
             cell::asexuallyReproduce(
                 &scratch[selectedCell],
                 &mut new_heritable_data[index],
                 stepsPerGen,
                 mutationRate,
+                innerNodes,
+                rng,
             );
+            if inheritActivation {
+                new_heritable_data[index]
+                    .get_header_mut()
+                    .mutate_activations(mutationRate, rng);
+            }
 
-            self.neuron_data[index] =
-                NeuronData::new(NeuralNet::new(new_heritable_data[index].get_footer()));
+            self.neuron_data[index] = new_neuron_data(
+                new_heritable_data[index].get_header(),
+                new_heritable_data[index].get_footer(),
+                config,
+            );
 
             self.misc_data[index] = MiscData::new(new_heritable_data[index].get_footer());
         }
+
+        parents
     }
 
     pub fn resolveDead(&mut self, grid: &mut Grid) {
@@ -181,12 +431,53 @@ impl Population {
         self.deathSize
     }
 
-    pub fn getMutMoveQueue(&mut self) -> &mut [(usize, (GridValueT, GridValueT))] {
+    pub fn getMutMoveQueue(&mut self) -> &mut [MoveRequest] {
         &mut self.moveQueue
     }
 
+    //Kills are resolved in queue order, so a cell killed earlier in the step cannot kill
+    //Returns the killer and victim of every kill
+    pub fn resolveKills(&mut self, size: usize, grid: &Grid) -> Vec<(usize, usize)> {
+        let (width, height) = grid.get_dimensions();
+        let mut kills = Vec::new();
+
+        for index in 0..size {
+            let (killerIndex, _, wantsKill) = self.moveQueue[index];
+            if !wantsKill || !self.misc_data[killerIndex].isAlive {
+                continue;
+            }
+
+            let killer = &self.movement_data[killerIndex];
+            let (dx, dy) = killer.lastMoveDir.get_move_offset();
+            let (targetX, targetY) = (
+                killer.x as isize + dx as isize,
+                killer.y as isize + dy as isize,
+            );
+
+            if targetX < 0 || targetY < 0 || targetX >= width as isize || targetY >= height as isize
+            {
+                continue;
+            }
+
+            if let Some(victim) = grid.get_occupant(targetX as GridValueT, targetY as GridValueT) {
+                if victim != killerIndex && self.misc_data[victim].isAlive {
+                    //Marked dead immediately so it can neither kill nor be killed again this step,
+                    //resolveDead frees its tile
+                    self.misc_data[victim].isAlive = false;
+                    self.addToDeathQueue(victim);
+                    kills.push((killerIndex, victim));
+                }
+            }
+        }
+
+        kills
+    }
+
     //size is the amount of entries to process
-    pub fn resolveMoveQueue(&mut self, size: usize, grid: &mut Grid) {
+    //Returns the cell, old position and new position of every cell that changed tile
+    pub fn resolveMoveQueue(&mut self, size: usize, grid: &mut Grid) -> Vec<CellMove> {
+        let mut moves = Vec::new();
+
         for index in 0..size {
             let moverIndex = self.moveQueue[index].0;
             if self.misc_data[moverIndex].isAlive {
@@ -195,11 +486,11 @@ impl Population {
 
                 grid.set_occupant(moverMovementData.x, moverMovementData.y, None);
 
-                if grid.get_occupant(newX, newY).is_none() {
-                } else if grid.get_occupant(newX, moverMovementData.y).is_none() {
+                if grid.is_free(newX, newY) {
+                } else if grid.is_free(newX, moverMovementData.y) {
                     //Changes X, but not Y pos
                     newY = moverMovementData.y;
-                } else if grid.get_occupant(moverMovementData.x, newY).is_none() {
+                } else if grid.is_free(moverMovementData.x, newY) {
                     newX = moverMovementData.x;
                 } else {
                     newX = moverMovementData.x;
@@ -209,6 +500,7 @@ impl Population {
                 grid.set_occupant(newX, newY, Some(moverIndex));
 
                 if !(newY == moverMovementData.y && newX == moverMovementData.x) {
+                    moves.push((moverIndex, moverMovementData.getCoords(), (newX, newY)));
                     moverMovementData.lastMoveDir = Direction::get_dir_from_offset((
                         newX as isize - moverMovementData.x as isize,
                         newY as isize - moverMovementData.y as isize,
@@ -218,13 +510,15 @@ impl Population {
                 moverMovementData.setCoords((newX, newY));
             }
         }
+
+        moves
     }
 
-    pub fn assignRandom(&mut self, grid: &mut Grid) {
+    pub fn assignRandom<R: Rng>(&mut self, grid: &mut Grid, rng: &mut R) {
         assert!(self.size <= grid.get_dimensions().0 * grid.get_dimensions().1);
 
         for index in 0..self.size {
-            let coords = grid.find_random_unoccupied();
+            let coords = grid.find_random_unoccupied(rng);
 
             self.movement_data[index].setCoords(coords);
 
@@ -249,7 +543,7 @@ impl Population {
         &mut [NeuronData],
         DstSliceMut<HeritableData, Gene>,
         &mut [MiscData],
-        &mut [(usize, (GridValueT, GridValueT))],
+        &mut [MoveRequest],
     ) {
         (
             &mut self.movement_data,
@@ -284,6 +578,10 @@ impl Population {
         &mut self.neuron_data
     }
 
+    pub fn get_heritable_data(&self) -> &DstArray<HeritableData, Gene> {
+        &self.heritable_data
+    }
+
     pub fn get_mut_heritable_data(&mut self) -> DstSliceMut<HeritableData, Gene> {
         self.heritable_data.get_mut_slice(0, self.size)
     }
@@ -305,6 +603,23 @@ impl Population {
     }
 }
 
+fn new_neuron_data(heritable: &HeritableData, genome: &[Gene], config: &Config) -> NeuronData {
+    let (inner, output) = if config.get_inherit_activation() {
+        heritable.get_activations()
+    } else {
+        (
+            config.get_inner_activation(),
+            config.get_output_activation(),
+        )
+    };
+
+    NeuronData::new(
+        NeuralNet::new(genome, config.get_inner_nodes())
+            .with_memory(config.get_inner_memory())
+            .with_activations(inner, output),
+    )
+}
+
 impl std::fmt::Debug for Population {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Population")
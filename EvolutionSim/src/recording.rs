@@ -0,0 +1,242 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use crate::{
+    frame::{Frame, ImageFormat},
+    grid::Grid,
+    observer::{Context, Observer},
+    population::Population,
+    simulation::{GenerationReport, StepReport},
+    Config, TimeT,
+};
+
+struct RecordedStep {
+    step: TimeT,
+    file: String,
+    //Indices into the kill schedule of the events triggered on this step
+    kill_events: Vec<usize>,
+    zone_deaths: usize,
+    cell_deaths: usize,
+}
+
+//Every step of one generation as numbered frames, with a manifest so they can be assembled elsewhere
+pub struct Recording {
+    dir: String,
+    generation: TimeT,
+    format: ImageFormat,
+    steps: Vec<RecordedStep>,
+}
+
+impl Recording {
+    pub fn start(base: &str, generation: TimeT, format: ImageFormat) -> io::Result<Recording> {
+        let dir = format!("{}/gen_{:05}", base, generation);
+        fs::create_dir_all(&dir)?;
+
+        Ok(Recording {
+            dir,
+            generation,
+            format,
+            steps: Vec::new(),
+        })
+    }
+
+    pub fn get_dir(&self) -> &str {
+        &self.dir
+    }
+
+    pub fn capture(
+        &mut self,
+        config: &Config,
+        grid: &Grid,
+        population: &Population,
+        step: TimeT,
+        zone_deaths: usize,
+        cell_deaths: usize,
+    ) -> io::Result<()> {
+        let file = format!("frame_{:04}.{}", self.steps.len(), self.format.get_name());
+        Frame::render(config, grid, population, step)
+            .save(&format!("{}/{}", self.dir, file), self.format)?;
+
+        let kill_events = config
+            .get_kill_schedule()
+            .get_events()
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| event.is_triggered(step, config.get_steps_per_gen()))
+            .map(|(index, _)| index)
+            .collect();
+
+        self.steps.push(RecordedStep {
+            step,
+            file,
+            kill_events,
+            zone_deaths,
+            cell_deaths,
+        });
+
+        Ok(())
+    }
+
+    pub fn finish(self, config: &Config) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(format!("{}/manifest.json", self.dir))?);
+        self.write_manifest(config, &mut writer)?;
+        writer.flush()
+    }
+
+    fn write_manifest<W: Write>(&self, config: &Config, writer: &mut W) -> io::Result<()> {
+        let events: Vec<String> = config
+            .get_kill_schedule()
+            .get_events()
+            .iter()
+            .map(|event| format!("\"{}\"", event.to_string().replace('"', "\\\"")))
+            .collect();
+
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"generation\": {},", self.generation)?;
+        writeln!(writer, "  \"format\": \"{}\",", self.format.get_name())?;
+        writeln!(writer, "  \"kill_schedule\": [{}],", events.join(", "))?;
+        writeln!(writer, "  \"frames\": [")?;
+
+        for (index, step) in self.steps.iter().enumerate() {
+            let kill_events: Vec<String> = step
+                .kill_events
+                .iter()
+                .map(|event| event.to_string())
+                .collect();
+
+            writeln!(
+                writer,
+                "    {{\"step\": {}, \"file\": \"{}\", \"kill_events\": [{}], \"zone_deaths\": {}, \"cell_deaths\": {}}}{}",
+                step.step,
+                step.file,
+                kill_events.join(", "),
+                step.zone_deaths,
+                step.cell_deaths,
+                if index + 1 == self.steps.len() { "" } else { "," }
+            )?;
+        }
+
+        writeln!(writer, "  ]")?;
+        writeln!(writer, "}}")
+    }
+}
+
+//Records every generation chosen with --record-gens
+#[derive(Default)]
+pub struct Recorder {
+    recording: Option<Recording>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+}
+
+impl Observer for Recorder {
+    fn step_start(&mut self, context: &Context) {
+        if context.step != 0 || !context.config.is_recorded(context.generation) {
+            return;
+        }
+
+        match Recording::start(
+            context.config.get_record_dir(),
+            context.generation,
+            context.config.get_frame_format(),
+        ) {
+            Ok(recording) => self.recording = Some(recording),
+            Err(err) => println!(
+                "Error: Failed to start recording generation {}: {}",
+                context.generation, err
+            ),
+        }
+    }
+
+    fn step_end(&mut self, context: &Context, report: &StepReport) {
+        if let Some(recording) = &mut self.recording {
+            if let Err(err) = recording.capture(
+                context.config,
+                context.grid,
+                context.population,
+                report.step,
+                report.killed_by_zones,
+                report.killed_by_cells,
+            ) {
+                println!(
+                    "Error: Failed to record step {}, stopping: {}",
+                    report.step, err
+                );
+                self.recording = None;
+            }
+        }
+    }
+
+    fn generation_end(&mut self, context: &Context, _report: &GenerationReport) {
+        if let Some(recording) = self.recording.take() {
+            let dir = recording.get_dir().to_string();
+
+            match recording.finish(context.config) {
+                Ok(()) => println!("Recorded generation to {}", dir),
+                Err(err) => println!("Error: Failed to write the manifest in {}: {}", dir, err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::Recording;
+    use crate::{
+        config::Config,
+        frame::ImageFormat,
+        grid::Grid,
+        kill_schedule::KillSchedule,
+        population::Population,
+        rng::{derive_rng, Stream},
+    };
+
+    #[test]
+    fn frames_and_manifest() {
+        let mut config = Config::new(5, 4, 8, 8, 0.0, 10, false).unwrap();
+        config.set_kill_schedule(KillSchedule::parse("3 circle(0.5, 0.5, 0.25)").unwrap());
+        let config = Rc::new(config);
+
+        let mut grid = Grid::new(8, 8);
+        let population = Population::new(
+            &config,
+            &mut grid,
+            &mut derive_rng(5, Stream::Genesis, 0, 0, 0),
+        );
+
+        let base = std::env::temp_dir().join(format!("recording_test_{}", std::process::id()));
+        let base = base.to_str().unwrap();
+
+        let mut recording = Recording::start(base, 7, ImageFormat::Ppm).unwrap();
+        let dir = recording.get_dir().to_string();
+        assert!(dir.ends_with("gen_00007"));
+
+        for step in 2..5 {
+            recording
+                .capture(&config, &grid, &population, step, (step == 3) as usize, 0)
+                .unwrap();
+        }
+        recording.finish(&config).unwrap();
+
+        let manifest = std::fs::read_to_string(format!("{}/manifest.json", dir)).unwrap();
+        assert!(manifest.contains("\"generation\": 7,"));
+        assert!(manifest.contains("\"kill_schedule\": [\"3 circle(0.5, 0.5, 0.25)\"],"));
+        assert!(manifest.contains(
+            "{\"step\": 3, \"file\": \"frame_0001.ppm\", \"kill_events\": [0], \"zone_deaths\": 1, \"cell_deaths\": 0},"
+        ));
+        assert!(
+            manifest.contains("\"step\": 4, \"file\": \"frame_0002.ppm\", \"kill_events\": [],")
+        );
+        assert!(std::path::Path::new(&format!("{}/frame_0002.ppm", dir)).exists());
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
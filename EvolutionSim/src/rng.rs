@@ -0,0 +1,74 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::TimeT;
+
+pub type SimRng = StdRng;
+
+//Every random decision draws from a stream keyed by what it is deciding, so a run only depends on
+//the seed, and not on thread count or the order in which cells are processed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Genesis = 0,
+    Placement,
+    Movement,
+    Reproduction,
+    Kill,
+    Barriers,
+    Diversity,
+}
+
+pub fn derive_rng(
+    seed: u64,
+    stream: Stream,
+    generation: TimeT,
+    step: TimeT,
+    index: usize,
+) -> SimRng {
+    let mut key = [0; 32];
+
+    key[0..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..16].copy_from_slice(&((stream as u64) | ((generation as u64) << 8)).to_le_bytes());
+    key[16..24].copy_from_slice(&(step as u64).to_le_bytes());
+    key[24..32].copy_from_slice(&(index as u64).to_le_bytes());
+
+    SimRng::from_seed(key)
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use scoped_threadpool::Pool;
+
+    use super::{derive_rng, Stream};
+    use crate::{computeMovements, config::Config, grid::Grid, population::Population};
+
+    fn run_steps(threads: u32) -> Vec<(usize, (usize, usize))> {
+        let mut config = Config::new(300, 12, 32, 32, 0.1, 40, false).unwrap();
+        config.set_seed(1234);
+        let config = Rc::new(config);
+
+        let mut grid = Grid::new(config.get_grid_width(), config.get_grid_height());
+        let mut rng = derive_rng(config.get_seed(), Stream::Genesis, 0, 0, 0);
+        let mut population = Population::new(&config, &mut grid, &mut rng);
+
+        let mut threadpool = Pool::new(threads);
+
+        let mut positions = Vec::new();
+        for step in 0..10 {
+            let size = computeMovements(&config, &mut threadpool, &mut population, &grid, 0, step);
+            population.resolveMoveQueue(size, &mut grid);
+        }
+        for index in 0..config.get_pop_size() {
+            positions.push((index, population.getCellMovementData(index).getCoords()));
+        }
+
+        positions
+    }
+
+    #[test]
+    fn thread_count_independent() {
+        assert_eq!(run_steps(1), run_steps(2));
+        assert_eq!(run_steps(1), run_steps(7));
+    }
+}
@@ -0,0 +1,295 @@
+use std::fmt::Display;
+
+use crate::{grid::GridValueT, spec::Spec};
+
+//Decides which surviving cells get to reproduce at the end of a generation
+//
+//Positions are normalized so that (0, 0) is the south-west corner of the grid and (1, 1) is the north-east corner,
+//which lets the same criterion be used for any grid size
+pub trait SelectionCriterion: Display {
+    fn is_selected(&self, x: f32, y: f32) -> bool;
+}
+
+pub fn normalize_coords(
+    coords: (GridValueT, GridValueT),
+    dimensions: (GridValueT, GridValueT),
+) -> (f32, f32) {
+    (
+        coords.0 as f32 / dimensions.0 as f32,
+        coords.1 as f32 / dimensions.1 as f32,
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Edge {
+    pub fn from_name(name: &str) -> Option<Edge> {
+        match name {
+            "north" | "n" => Some(Edge::North),
+            "east" | "e" => Some(Edge::East),
+            "south" | "s" => Some(Edge::South),
+            "west" | "w" => Some(Edge::West),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Edge::North => "north",
+            Edge::East => "east",
+            Edge::South => "south",
+            Edge::West => "west",
+        }
+    }
+}
+
+pub struct Circle {
+    pub center: (f32, f32),
+    pub radius: f32,
+}
+
+impl SelectionCriterion for Circle {
+    fn is_selected(&self, x: f32, y: f32) -> bool {
+        let (dx, dy) = (x - self.center.0, y - self.center.1);
+        dx * dx + dy * dy <= self.radius * self.radius
+    }
+}
+
+impl Display for Circle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "circle({}, {}, {})",
+            self.center.0, self.center.1, self.radius
+        )
+    }
+}
+
+//Square regions with sides of `size` in each corner
+pub struct Corners {
+    pub size: f32,
+}
+
+impl SelectionCriterion for Corners {
+    fn is_selected(&self, x: f32, y: f32) -> bool {
+        (x < self.size || x > 1.0 - self.size) && (y < self.size || y > 1.0 - self.size)
+    }
+}
+
+impl Display for Corners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "corners({})", self.size)
+    }
+}
+
+pub struct EdgeBand {
+    pub edge: Edge,
+    pub width: f32,
+}
+
+impl SelectionCriterion for EdgeBand {
+    fn is_selected(&self, x: f32, y: f32) -> bool {
+        match self.edge {
+            Edge::North => y > 1.0 - self.width,
+            Edge::East => x > 1.0 - self.width,
+            Edge::South => y < self.width,
+            Edge::West => x < self.width,
+        }
+    }
+}
+
+impl Display for EdgeBand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "edge({}, {})", self.edge.get_name(), self.width)
+    }
+}
+
+pub struct MinDistance {
+    pub point: (f32, f32),
+    pub distance: f32,
+}
+
+impl SelectionCriterion for MinDistance {
+    fn is_selected(&self, x: f32, y: f32) -> bool {
+        let (dx, dy) = (x - self.point.0, y - self.point.1);
+        dx * dx + dy * dy >= self.distance * self.distance
+    }
+}
+
+impl Display for MinDistance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "distance({}, {}, {})",
+            self.point.0, self.point.1, self.distance
+        )
+    }
+}
+
+pub struct And(pub Vec<Box<dyn SelectionCriterion>>);
+
+impl SelectionCriterion for And {
+    fn is_selected(&self, x: f32, y: f32) -> bool {
+        self.0.iter().all(|criterion| criterion.is_selected(x, y))
+    }
+}
+
+impl Display for And {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_combination(f, "and", &self.0)
+    }
+}
+
+pub struct Or(pub Vec<Box<dyn SelectionCriterion>>);
+
+impl SelectionCriterion for Or {
+    fn is_selected(&self, x: f32, y: f32) -> bool {
+        self.0.iter().any(|criterion| criterion.is_selected(x, y))
+    }
+}
+
+impl Display for Or {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_combination(f, "or", &self.0)
+    }
+}
+
+fn write_combination(
+    f: &mut std::fmt::Formatter<'_>,
+    name: &str,
+    criteria: &[Box<dyn SelectionCriterion>],
+) -> std::fmt::Result {
+    write!(f, "{}(", name)?;
+    for (index, criterion) in criteria.iter().enumerate() {
+        if index != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", criterion)?;
+    }
+    write!(f, ")")
+}
+
+//The original rule, cells in the outer quarters of the x axis reproduce
+//
+//The quarters are found with integer division of the width like the original, so the bands depend on
+//it when it isn't a multiple of 4
+pub fn default_criterion(grid_width: GridValueT) -> Box<dyn SelectionCriterion> {
+    let (west, east) = (grid_width / 4, 3 * grid_width / 4);
+
+    Box::new(Or(vec![
+        Box::new(EdgeBand {
+            edge: Edge::West,
+            width: west as f32 / grid_width as f32,
+        }),
+        Box::new(EdgeBand {
+            edge: Edge::East,
+            width: 1.0 - east as f32 / grid_width as f32,
+        }),
+    ]))
+}
+
+pub fn parse_criterion(input: &str) -> Result<Box<dyn SelectionCriterion>, String> {
+    from_spec(&Spec::parse(input)?)
+}
+
+fn from_spec(spec: &Spec) -> Result<Box<dyn SelectionCriterion>, String> {
+    let criterion: Box<dyn SelectionCriterion> = match spec.name.as_str() {
+        "circle" => {
+            spec.expect_args(3)?;
+            Box::new(Circle {
+                center: (spec.number(0)?, spec.number(1)?),
+                radius: spec.number(2)?,
+            })
+        }
+        "corners" => {
+            spec.expect_args(1)?;
+            Box::new(Corners {
+                size: spec.number(0)?,
+            })
+        }
+        "edge" => {
+            spec.expect_args(2)?;
+            let edge = Edge::from_name(spec.word(0)?)
+                .ok_or_else(|| format!("Unknown edge `{}`", spec.word(0).unwrap()))?;
+            Box::new(EdgeBand {
+                edge,
+                width: spec.number(1)?,
+            })
+        }
+        "distance" => {
+            spec.expect_args(3)?;
+            Box::new(MinDistance {
+                point: (spec.number(0)?, spec.number(1)?),
+                distance: spec.number(2)?,
+            })
+        }
+        "and" | "or" => {
+            if spec.args.len() < 2 {
+                return Err(format!("`{}` needs at least 2 criteria", spec.name));
+            }
+
+            let mut criteria = Vec::with_capacity(spec.args.len());
+            for index in 0..spec.args.len() {
+                criteria.push(from_spec(&spec.call(index)?)?);
+            }
+
+            if spec.name == "and" {
+                Box::new(And(criteria))
+            } else {
+                Box::new(Or(criteria))
+            }
+        }
+        name => return Err(format!("Unknown selection criterion `{}`", name)),
+    };
+
+    Ok(criterion)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{default_criterion, normalize_coords, parse_criterion};
+
+    #[test]
+    fn default_matches_quarters() {
+        for width in [1, 2, 7, 10, 13, 200, 201] {
+            let criterion = default_criterion(width);
+
+            for x in 0..width {
+                let (nx, ny) = normalize_coords((x, 3), (width, 8));
+                assert_eq!(
+                    criterion.is_selected(nx, ny),
+                    x < width / 4 || x > 3 * width / 4,
+                    "x {} of width {}",
+                    x,
+                    width
+                );
+            }
+
+            let reparsed = parse_criterion(&criterion.to_string()).unwrap();
+            assert_eq!(reparsed.to_string(), criterion.to_string());
+        }
+    }
+
+    #[test]
+    fn combinations() {
+        let criterion =
+            parse_criterion("and(circle(0.5, 0.5, 0.4), distance(0.5, 0.5, 0.2))").unwrap();
+
+        assert!(!criterion.is_selected(0.5, 0.5));
+        assert!(criterion.is_selected(0.8, 0.5));
+        assert!(!criterion.is_selected(0.95, 0.5));
+
+        let corners = parse_criterion("corners(0.1)").unwrap();
+        assert!(corners.is_selected(0.05, 0.95));
+        assert!(!corners.is_selected(0.05, 0.5));
+
+        assert!(parse_criterion("edge(up, 0.2)").is_err());
+        assert!(parse_criterion("or(corners(0.1))").is_err());
+    }
+}
@@ -0,0 +1,473 @@
+use custom_dst::{DstArray, MaybeUninitDstArray};
+use scoped_threadpool::Pool;
+
+use crate::{
+    cell::HeritableData,
+    computeMovements,
+    gene::Gene,
+    grid::Grid,
+    kill_schedule::KillEvent,
+    observer::{Context, DeathCause, Observer},
+    population::Population,
+    rng::{derive_rng, Stream},
+    selection::normalize_coords,
+    stats::{ConnectionStats, GenerationStats},
+    Config, TimeT,
+};
+
+pub struct StepReport {
+    //Index of the step within its generation
+    pub step: TimeT,
+    pub killed_by_cells: usize,
+    pub killed_by_zones: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenerationOutcome {
+    Reproduced,
+    //Every cell died before the generation ended
+    Extinct,
+    //Cells survived, but none of them were selected
+    NoReproducers,
+}
+
+pub struct GenerationReport {
+    pub stats: GenerationStats,
+    //Cells alive when the generation ended
+    pub living: usize,
+    pub outcome: GenerationOutcome,
+}
+
+//Owns everything needed to run the simulation, front-ends only drive it and look at its state
+pub struct Simulation {
+    config: Config,
+    grid: Grid,
+    population: Population,
+    scratch: DstArray<HeritableData, Gene>,
+    threadpool: Pool,
+    generation: TimeT,
+    step: TimeT,
+    resets: usize,
+    stats: GenerationStats,
+    connections: ConnectionStats,
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl Simulation {
+    pub fn new(config: Config) -> Result<Simulation, String> {
+        Simulation::create(config, 0, None)
+    }
+
+    //Continues from a saved generation
+    pub fn from_snapshot(
+        config: Config,
+        generation: TimeT,
+        heritable_data: DstArray<HeritableData, Gene>,
+    ) -> Result<Simulation, String> {
+        Simulation::create(config, generation, Some(heritable_data))
+    }
+
+    fn create(
+        config: Config,
+        generation: TimeT,
+        heritable_data: Option<DstArray<HeritableData, Gene>>,
+    ) -> Result<Simulation, String> {
+        let mut grid = Grid::new(config.get_grid_width(), config.get_grid_height());
+        config.get_barriers().apply(
+            &mut grid,
+            &mut derive_rng(config.get_seed(), Stream::Barriers, 0, 0, 0),
+        );
+
+        if grid.get_free_count() < config.get_pop_size() {
+            return Err(format!(
+                "{} barrier layout leaves room for only {} cells",
+                config.get_barriers().get_name(),
+                grid.get_free_count()
+            ));
+        }
+
+        let population = match heritable_data {
+            Some(heritable_data) => Population::from_heritable(
+                &config,
+                &mut grid,
+                heritable_data,
+                &mut derive_rng(config.get_seed(), Stream::Placement, generation, 0, 0),
+            ),
+            None => Population::new(
+                &config,
+                &mut grid,
+                &mut derive_rng(config.get_seed(), Stream::Genesis, 0, 0, 0),
+            ),
+        };
+
+        let scratch = MaybeUninitDstArray::<HeritableData, Gene>::new(
+            config.get_genome_size(),
+            config.get_pop_size(),
+        );
+        //Safety: this will be written to before it is ever read from
+        let scratch = unsafe { scratch.assume_init() };
+
+        let stats = GenerationStats::new(generation, config.get_kill_schedule().get_events().len());
+        let connections =
+            ConnectionStats::collect(&population, config.get_pop_size(), config.get_inner_nodes());
+
+        Ok(Simulation {
+            threadpool: Pool::new(std::thread::available_parallelism().unwrap().get() as u32),
+            config,
+            grid,
+            population,
+            scratch,
+            generation,
+            step: 0,
+            resets: 0,
+            stats,
+            connections,
+            observers: Vec::new(),
+        })
+    }
+
+    //Observers are notified in the order they were added, after the statistics of the generation
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    fn notify<F: FnMut(&mut dyn Observer, &Context)>(&mut self, mut callback: F) {
+        let context = Context {
+            config: &self.config,
+            grid: &self.grid,
+            population: &self.population,
+            generation: self.generation,
+            step: self.step,
+        };
+
+        callback(&mut self.stats, &context);
+        for observer in self.observers.iter_mut() {
+            callback(observer.as_mut(), &context);
+        }
+    }
+
+    //Does nothing once the population died out
+    pub fn step(&mut self) -> StepReport {
+        let step = self.step;
+        if self.is_extinct() {
+            return StepReport {
+                step,
+                killed_by_cells: 0,
+                killed_by_zones: 0,
+            };
+        }
+
+        self.notify(|observer, context| observer.step_start(context));
+
+        let size = computeMovements(
+            &self.config,
+            &mut self.threadpool,
+            &mut self.population,
+            &self.grid,
+            self.generation,
+            step,
+        );
+        let kills = self.population.resolveKills(size, &self.grid);
+        self.population.resolveDead(&mut self.grid);
+        let moves = self.population.resolveMoveQueue(size, &mut self.grid);
+
+        let zone_deaths =
+            determine_deaths(&self.config, self.generation, step, &mut self.population);
+        self.population.resolveDead(&mut self.grid);
+
+        for (killer, victim) in &kills {
+            self.notify(|observer, _| observer.cell_died(*victim, DeathCause::Cell(*killer)));
+        }
+        for (cell, from, to) in &moves {
+            self.notify(|observer, _| observer.cell_moved(*cell, *from, *to));
+        }
+        for (cell, event) in &zone_deaths {
+            self.notify(|observer, _| observer.cell_died(*cell, DeathCause::Zone(*event)));
+        }
+
+        let report = StepReport {
+            step,
+            killed_by_cells: kills.len(),
+            killed_by_zones: zone_deaths.len(),
+        };
+        self.notify(|observer, context| observer.step_end(context, &report));
+
+        self.step += 1;
+
+        report
+    }
+
+    pub fn is_extinct(&self) -> bool {
+        self.population.getLivingIndices().is_empty()
+    }
+
+    pub fn is_generation_over(&self) -> bool {
+        self.step == self.config.get_steps_per_gen() || self.is_extinct()
+    }
+
+    //Selects the reproducers and breeds the next generation from them
+    //
+    //Nothing is bred if the population died out or no cell was selected, and the simulation stays on
+    //this generation until it is reset
+    pub fn finish_generation(&mut self) -> GenerationReport {
+        let living = self.population.getLivingIndices().len();
+        let reproducers = determine_reproducers(&self.config, &self.population);
+        let killEvents = self.config.get_kill_schedule().get_events().len();
+
+        let mut stats = std::mem::replace(
+            &mut self.stats,
+            GenerationStats::new(self.generation, killEvents),
+        );
+        stats.finish(
+            &self.population,
+            self.config.get_pop_size(),
+            reproducers.len(),
+            &self.connections,
+            &mut derive_rng(
+                self.config.get_seed(),
+                Stream::Diversity,
+                self.generation,
+                0,
+                0,
+            ),
+        );
+
+        let outcome = if living == 0 {
+            GenerationOutcome::Extinct
+        } else if reproducers.is_empty() {
+            GenerationOutcome::NoReproducers
+        } else {
+            GenerationOutcome::Reproduced
+        };
+
+        let report = GenerationReport {
+            stats,
+            living,
+            outcome,
+        };
+        self.notify(|observer, context| observer.generation_end(context, &report));
+
+        if outcome == GenerationOutcome::Reproduced {
+            self.grid.reset();
+            let parents = self.population.reproduce(
+                &mut self.scratch,
+                &self.config,
+                reproducers,
+                &mut self.grid,
+                &mut derive_rng(
+                    self.config.get_seed(),
+                    Stream::Reproduction,
+                    self.generation,
+                    0,
+                    0,
+                ),
+            );
+
+            self.generation += 1;
+            self.step = 0;
+            self.stats = GenerationStats::new(self.generation, killEvents);
+            self.connections = ConnectionStats::collect(
+                &self.population,
+                self.config.get_pop_size(),
+                self.config.get_inner_nodes(),
+            );
+
+            for (child, (first, second)) in parents.iter().enumerate() {
+                self.notify(|observer, _| {
+                    observer.reproduced(*first, child);
+                    if second != first {
+                        observer.reproduced(*second, child);
+                    }
+                });
+            }
+        }
+
+        report
+    }
+
+    //Runs the rest of the current generation, then finishes it
+    pub fn run_generation(&mut self) -> GenerationReport {
+        while !self.is_generation_over() {
+            self.step();
+        }
+
+        self.finish_generation()
+    }
+
+    //Starts over from a new random population at generation 0
+    pub fn reset(&mut self) {
+        self.resets += 1;
+        self.generation = 0;
+        self.step = 0;
+
+        self.grid.reset();
+        self.population.genRandom(
+            &self.config,
+            &mut self.grid,
+            &mut derive_rng(self.config.get_seed(), Stream::Genesis, 0, 0, self.resets),
+        );
+
+        self.stats = GenerationStats::new(0, self.config.get_kill_schedule().get_events().len());
+        self.connections = ConnectionStats::collect(
+            &self.population,
+            self.config.get_pop_size(),
+            self.config.get_inner_nodes(),
+        );
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn population(&self) -> &Population {
+        &self.population
+    }
+
+    //Statistics of the generation so far
+    pub fn stats(&self) -> &GenerationStats {
+        &self.stats
+    }
+
+    pub fn connections(&self) -> &ConnectionStats {
+        &self.connections
+    }
+
+    pub fn get_generation(&self) -> TimeT {
+        self.generation
+    }
+
+    //Steps already taken in the current generation
+    pub fn get_step(&self) -> TimeT {
+        self.step
+    }
+}
+
+pub fn determine_reproducers(config: &Config, pop: &Population) -> Vec<usize> {
+    let dimensions = (config.get_grid_width(), config.get_grid_height());
+    let criterion = config.get_selection();
+
+    let mut reproducers = Vec::new();
+    for cellIndex in pop.getLivingIndices() {
+        let (x, y) = normalize_coords(pop.getCellMovementData(cellIndex).getCoords(), dimensions);
+        if criterion.is_selected(x, y) {
+            reproducers.push(cellIndex);
+        }
+    }
+
+    reproducers
+}
+
+//Queues the cells killed by the kill schedule on this step, returns each of them with the index of
+//the event that killed it
+pub fn determine_deaths(
+    config: &Config,
+    generation: TimeT,
+    step: TimeT,
+    pop: &mut Population,
+) -> Vec<(usize, usize)> {
+    let steps_per_gen = config.get_steps_per_gen();
+    let events: Vec<(usize, &KillEvent)> = config
+        .get_kill_schedule()
+        .get_events()
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| event.is_triggered(step, steps_per_gen))
+        .collect();
+
+    let mut deaths = Vec::new();
+    if !events.is_empty() {
+        let dimensions = (config.get_grid_width(), config.get_grid_height());
+        let mut rng = derive_rng(config.get_seed(), Stream::Kill, generation, step, 0);
+
+        for index in pop.getLivingIndices() {
+            let (x, y) = normalize_coords(pop.getCellMovementData(index).getCoords(), dimensions);
+
+            if let Some((event, _)) = events.iter().find(|(_, event)| event.kills(x, y, &mut rng)) {
+                deaths.push((index, *event));
+                pop.addToDeathQueue(index);
+            }
+        }
+    }
+
+    deaths
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{GenerationOutcome, Simulation};
+    use crate::{config::Config, kill_schedule::KillSchedule, selection};
+
+    fn config() -> Config {
+        let mut config = Config::new(100, 8, 24, 24, 0.1, 20, false).unwrap();
+        config.set_seed(99);
+        config.set_selection(selection::parse_criterion("edge(east, 0.5)").unwrap());
+        config.set_kill_schedule(KillSchedule::parse("").unwrap());
+        config
+    }
+
+    #[test]
+    fn generations_are_repeatable() {
+        let mut first = Simulation::new(Rc::new(config())).unwrap();
+        let mut second = Simulation::new(Rc::new(config())).unwrap();
+
+        for _ in 0..3 {
+            let (a, b) = (first.run_generation(), second.run_generation());
+            assert_eq!(a.outcome, GenerationOutcome::Reproduced);
+            assert_eq!(
+                (a.living, a.stats.reproducers),
+                (b.living, b.stats.reproducers)
+            );
+        }
+        assert_eq!(first.get_generation(), 3);
+        assert_eq!(first.get_step(), 0);
+
+        for _ in 0..5 {
+            first.step();
+            second.step();
+        }
+        assert_eq!(first.get_step(), 5);
+        for index in 0..100 {
+            assert_eq!(
+                first.population().getCellMovementData(index).getCoords(),
+                second.population().getCellMovementData(index).getCoords()
+            );
+        }
+
+        first.reset();
+        assert_eq!((first.get_generation(), first.get_step()), (0, 0));
+        assert_eq!(first.grid().get_free_count(), 24 * 24 - 100);
+    }
+
+    #[test]
+    fn steps_after_extinction() {
+        let mut config = config();
+        config.set_kill_schedule(KillSchedule::parse("0 rect(0, 0, 1, 1)").unwrap());
+        let mut simulation = Simulation::new(Rc::new(config)).unwrap();
+
+        assert_eq!(simulation.step().killed_by_zones, 100);
+        assert!(simulation.is_extinct() && simulation.is_generation_over());
+
+        let report = simulation.step();
+        assert_eq!((report.killed_by_cells, report.killed_by_zones), (0, 0));
+        assert_eq!(simulation.get_step(), 1);
+        assert_eq!(
+            simulation.finish_generation().outcome,
+            GenerationOutcome::Extinct
+        );
+    }
+
+    #[test]
+    fn no_room() {
+        let mut config = config();
+        config.set_barriers(crate::barrier::BarrierLayout::VerticalBar);
+        config.set_pop_size(24 * 24).unwrap();
+
+        assert!(Simulation::new(Rc::new(config)).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    io::{self, Read, Write},
+    mem::size_of,
+};
+
+use custom_dst::{DstArray, MaybeUninitDstArray};
+
+use crate::{cell::HeritableData, config::Config, gene::Gene, population::Population, TimeT};
+
+/* Snapshot layout, all values little endian
+ * Magic -> 8 bytes
+ * Version -> u32
+ * Config -> Config::serialize
+ * Generation -> u64
+ * Cells -> pop_size * (HeritableData::serialize + genome_length * Gene::serialize)
+ *
+ * Gene node ids depend on the node layout, so adding a node requires a new version
+ * A gene head of 0xFF is NodeID::Bias, the gene is a bias of its tail rather than a connection
+ */
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"EVOSNAP\0";
+pub const SNAPSHOT_VERSION: u32 = 7;
+
+pub fn write_snapshot<T: Write>(
+    writer: &mut T,
+    config: &Config,
+    generation: TimeT,
+    population: &Population,
+) -> io::Result<()> {
+    writer.write_all(&SNAPSHOT_MAGIC)?;
+    writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+
+    config.serialize(writer)?;
+
+    writer.write_all(&(generation as u64).to_le_bytes())?;
+
+    for index in 0..config.get_pop_size() {
+        let cell = population.getCellHeritableData(index);
+
+        cell.get_header().serialize(writer)?;
+        for gene in cell.get_footer() {
+            gene.serialize(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn read_snapshot<T: Read>(
+    reader: &mut T,
+) -> io::Result<(Config, TimeT, DstArray<HeritableData, Gene>)> {
+    let mut magic = [0; SNAPSHOT_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != SNAPSHOT_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a population snapshot",
+        ));
+    }
+
+    let mut buf4 = [0; size_of::<u32>()];
+    reader.read_exact(&mut buf4)?;
+    let version = u32::from_le_bytes(buf4);
+    if version != SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Unsupported snapshot version {} (expected {})",
+                version, SNAPSHOT_VERSION
+            ),
+        ));
+    }
+
+    let config = Config::deserialize(reader)?;
+
+    let mut buf8 = [0; size_of::<u64>()];
+    reader.read_exact(&mut buf8)?;
+    let generation = u64::from_le_bytes(buf8) as TimeT;
+
+    let mut heritable_data =
+        MaybeUninitDstArray::new(config.get_genome_size(), config.get_pop_size());
+
+    for index in 0..config.get_pop_size() {
+        heritable_data.write_header(
+            index,
+            HeritableData::deserialize(reader, config.get_steps_per_gen())?,
+        );
+
+        let mut gene_ptr = heritable_data.get_footer_element_ptr_mut(index, 0);
+
+        //SAFETY we know the size of the footer and we know the arr index, so this is safe
+        for _ in 0..config.get_genome_size() {
+            unsafe {
+                *gene_ptr = Gene::deserialize(reader, config.get_inner_nodes())?;
+                gene_ptr = gene_ptr.add(1);
+            }
+        }
+    }
+
+    //SAFETY every header and gene was written above, or we returned early
+    Ok((config, generation, unsafe { heritable_data.assume_init() }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{read_snapshot, write_snapshot};
+    use crate::{
+        config::Config,
+        grid::Grid,
+        population::Population,
+        rng::{derive_rng, Stream},
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut config = Config::new(50, 8, 16, 16, 0.1, 30, false).unwrap();
+        config.set_inner_nodes(7).unwrap();
+        let config = Rc::new(config);
+        let mut grid = Grid::new(config.get_grid_width(), config.get_grid_height());
+        let population = Population::new(
+            &config,
+            &mut grid,
+            &mut derive_rng(config.get_seed(), Stream::Genesis, 0, 0, 0),
+        );
+
+        let mut buffer = Vec::new();
+        write_snapshot(&mut buffer, &config, 7, &population).unwrap();
+
+        let (loaded, generation, heritable) = read_snapshot(&mut buffer.as_slice()).unwrap();
+
+        assert_eq!(generation, 7);
+        assert_eq!(loaded.get_pop_size(), config.get_pop_size());
+        assert_eq!(loaded.get_genome_size(), config.get_genome_size());
+        assert_eq!(loaded.get_steps_per_gen(), config.get_steps_per_gen());
+        assert_eq!(loaded.get_seed(), config.get_seed());
+        assert_eq!(loaded.get_inner_nodes(), 7);
+
+        for index in 0..config.get_pop_size() {
+            let original = population.getCellHeritableData(index);
+            let loaded = heritable.get_arr_element(index);
+
+            assert_eq!(
+                original.get_header().get_oscillator(),
+                loaded.get_header().get_oscillator()
+            );
+            assert_eq!(
+                original.get_header().get_activations(),
+                loaded.get_header().get_activations()
+            );
+            for (a, b) in original.get_footer().iter().zip(loaded.get_footer()) {
+                assert_eq!(a.gene, b.gene);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let buffer = [0u8; 64];
+        assert!(read_snapshot(&mut buffer.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    fmt::{Display, Write},
+    iter::Peekable,
+    str::Chars,
+};
+
+//Small call-style syntax used for describing shapes on the command line, eg. `or(edge(west, 0.25), circle(0.5, 0.5, 0.1))`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spec {
+    pub name: String,
+    pub args: Vec<SpecArg>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpecArg {
+    Number(f32),
+    Word(String),
+    Call(Spec),
+}
+
+impl Spec {
+    pub fn parse(input: &str) -> Result<Spec, String> {
+        let mut chars = input.chars().peekable();
+
+        let spec = match parse_arg(&mut chars)? {
+            SpecArg::Call(spec) => spec,
+            SpecArg::Word(name) => Spec {
+                name,
+                args: Vec::new(),
+            },
+            SpecArg::Number(number) => return Err(format!("Expected a name, found {}", number)),
+        };
+
+        skip_whitespace(&mut chars);
+        if let Some(c) = chars.next() {
+            return Err(format!("Unexpected '{}' after `{}`", c, spec));
+        }
+
+        Ok(spec)
+    }
+
+    pub fn expect_args(&self, count: usize) -> Result<(), String> {
+        if self.args.len() != count {
+            return Err(format!(
+                "`{}` takes {} arguments, found {}",
+                self.name,
+                count,
+                self.args.len()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn number(&self, index: usize) -> Result<f32, String> {
+        match self.args.get(index) {
+            Some(SpecArg::Number(number)) => Ok(*number),
+            _ => Err(format!(
+                "Argument {} of `{}` must be a number",
+                index + 1,
+                self.name
+            )),
+        }
+    }
+
+    pub fn word(&self, index: usize) -> Result<&str, String> {
+        match self.args.get(index) {
+            Some(SpecArg::Word(word)) => Ok(word),
+            _ => Err(format!(
+                "Argument {} of `{}` must be a name",
+                index + 1,
+                self.name
+            )),
+        }
+    }
+
+    //Bare names are treated as calls without arguments
+    pub fn call(&self, index: usize) -> Result<Spec, String> {
+        match self.args.get(index) {
+            Some(SpecArg::Call(spec)) => Ok(spec.clone()),
+            Some(SpecArg::Word(name)) => Ok(Spec {
+                name: name.clone(),
+                args: Vec::new(),
+            }),
+            _ => Err(format!(
+                "Argument {} of `{}` must be a shape",
+                index + 1,
+                self.name
+            )),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_arg(chars: &mut Peekable<Chars>) -> Result<SpecArg, String> {
+    skip_whitespace(chars);
+
+    let mut token = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || "_-+.".contains(*c)) {
+        token.push(c);
+    }
+
+    if token.is_empty() {
+        return match chars.peek() {
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err("Unexpected end of input".to_string()),
+        };
+    }
+
+    if let Ok(number) = token.parse::<f32>() {
+        return Ok(SpecArg::Number(number));
+    }
+
+    skip_whitespace(chars);
+    if chars.next_if_eq(&'(').is_none() {
+        return Ok(SpecArg::Word(token.to_lowercase()));
+    }
+
+    let mut args = Vec::new();
+
+    skip_whitespace(chars);
+    if chars.next_if_eq(&')').is_none() {
+        loop {
+            args.push(parse_arg(chars)?);
+
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(',') => {}
+                Some(')') => break,
+                Some(c) => {
+                    return Err(format!("Expected ',' or ')' in `{}`, found '{}'", token, c))
+                }
+                None => return Err(format!("Missing ')' for `{}`", token)),
+            }
+        }
+    }
+
+    Ok(SpecArg::Call(Spec {
+        name: token.to_lowercase(),
+        args,
+    }))
+}
+
+impl Display for Spec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if self.args.is_empty() {
+            return Ok(());
+        }
+
+        f.write_char('(')?;
+        for (index, arg) in self.args.iter().enumerate() {
+            if index != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        f.write_char(')')
+    }
+}
+
+impl Display for SpecArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecArg::Number(number) => write!(f, "{}", number),
+            SpecArg::Word(word) => f.write_str(word),
+            SpecArg::Call(spec) => write!(f, "{}", spec),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Spec, SpecArg};
+
+    #[test]
+    fn nested() {
+        let spec = Spec::parse("or(edge(West, 0.25), circle(0.5,0.5, .1))").unwrap();
+
+        assert_eq!(spec.name, "or");
+        assert_eq!(spec.args.len(), 2);
+        assert_eq!(
+            spec.call(0).unwrap().args,
+            vec![SpecArg::Word("west".to_string()), SpecArg::Number(0.25)]
+        );
+        assert_eq!(spec.call(1).unwrap().number(2).unwrap(), 0.1);
+        assert_eq!(
+            spec.to_string(),
+            "or(edge(west, 0.25), circle(0.5, 0.5, 0.1))"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Spec::parse("circle(0.5, 0.5").is_err());
+        assert!(Spec::parse("circle(0.5 0.5)").is_err());
+        assert!(Spec::parse("circle() extra").is_err());
+        assert!(Spec::parse("").is_err());
+    }
+}
@@ -0,0 +1,595 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufWriter, Write},
+    time::Instant,
+};
+
+use custom_dst::DstArray;
+use rand::Rng;
+
+use crate::{
+    cell::HeritableData,
+    gene::{Gene, NodeID},
+    observer::{Context, DeathCause, Observer},
+    population::Population,
+    simulation::GenerationReport,
+    TimeT,
+};
+
+pub struct GenerationStats {
+    pub generation: TimeT,
+    //Deaths caused by each event of the kill schedule, in schedule order
+    pub kill_deaths: Vec<usize>,
+    pub kill_forward_deaths: usize,
+    pub reproducers: usize,
+    pub survival_ratio: f32,
+    pub oscillator_mean: f32,
+    pub oscillator_std_dev: f32,
+    pub diversity: Diversity,
+    //Cells using each node, in layout order
+    pub node_presence: Vec<(NodeID, usize)>,
+    pub seconds: f64,
+    started: Instant,
+}
+
+impl GenerationStats {
+    pub fn new(generation: TimeT, kill_events: usize) -> GenerationStats {
+        GenerationStats {
+            generation,
+            kill_deaths: vec![0; kill_events],
+            kill_forward_deaths: 0,
+            reproducers: 0,
+            survival_ratio: 0.0,
+            oscillator_mean: 0.0,
+            oscillator_std_dev: 0.0,
+            diversity: Diversity::default(),
+            node_presence: Vec::new(),
+            seconds: 0.0,
+            started: Instant::now(),
+        }
+    }
+
+    //Fills in the values that describe the population at the end of the generation
+    pub fn finish<R: Rng>(
+        &mut self,
+        population: &Population,
+        pop_size: usize,
+        reproducers: usize,
+        connections: &ConnectionStats,
+        rng: &mut R,
+    ) {
+        self.reproducers = reproducers;
+        self.survival_ratio = population.getLivingIndices().len() as f32 / pop_size as f32;
+
+        let oscillators: Vec<f32> = (0..pop_size)
+            .map(|index| {
+                population
+                    .getCellHeritableData(index)
+                    .get_header()
+                    .get_oscillator() as f32
+            })
+            .collect();
+        self.oscillator_mean = oscillators.iter().sum::<f32>() / pop_size as f32;
+        self.oscillator_std_dev = (oscillators
+            .iter()
+            .map(|oscillator| (oscillator - self.oscillator_mean).powi(2))
+            .sum::<f32>()
+            / pop_size as f32)
+            .sqrt();
+
+        self.diversity = Diversity::collect(population.get_heritable_data(), pop_size, rng);
+        self.node_presence = connections.get_node_presence();
+        self.seconds = self.started.elapsed().as_secs_f64();
+    }
+}
+
+//Deaths are counted as they happen, everything else is filled in by finish
+impl Observer for GenerationStats {
+    fn cell_died(&mut self, _cell: usize, cause: DeathCause) {
+        match cause {
+            DeathCause::Cell(_) => self.kill_forward_deaths += 1,
+            DeathCause::Zone(event) => self.kill_deaths[event] += 1,
+        }
+    }
+}
+
+//Pairs compared for the mean Hamming distance, populations with fewer pairs compare all of them
+const HAMMING_SAMPLES: usize = 1024;
+
+#[derive(Clone, Debug, Default)]
+pub struct Diversity {
+    pub genomes: usize,
+    pub unique_genomes: usize,
+    //Mean count of differing bits between two genomes
+    pub mean_hamming: f32,
+    //Shannon entropy in bits of the gene values at each position of the genome
+    pub locus_entropy: Vec<f32>,
+}
+
+impl Diversity {
+    pub fn collect<R: Rng>(
+        genomes: &DstArray<HeritableData, Gene>,
+        pop_size: usize,
+        rng: &mut R,
+    ) -> Diversity {
+        let genome = |index: usize| genomes.get_arr_element(index).get_footer();
+
+        let unique: HashSet<Vec<u32>> = (0..pop_size)
+            .map(|index| genome(index).iter().map(|gene| gene.gene).collect())
+            .collect();
+
+        let distance = |first: usize, second: usize| -> u32 {
+            genome(first)
+                .iter()
+                .zip(genome(second))
+                .map(|(a, b)| (a.gene ^ b.gene).count_ones())
+                .sum()
+        };
+        let pairs = pop_size * pop_size.saturating_sub(1) / 2;
+        let mean_hamming = if pairs == 0 {
+            0.0
+        } else if pairs <= HAMMING_SAMPLES {
+            let mut total = 0u64;
+            for first in 0..pop_size {
+                for second in (first + 1)..pop_size {
+                    total += distance(first, second) as u64;
+                }
+            }
+            total as f32 / pairs as f32
+        } else {
+            let mut total = 0u64;
+            for _ in 0..HAMMING_SAMPLES {
+                let first = rng.gen_range(0..pop_size);
+                let second = (first + rng.gen_range(1..pop_size)) % pop_size;
+                total += distance(first, second) as u64;
+            }
+            total as f32 / HAMMING_SAMPLES as f32
+        };
+
+        let loci = if pop_size == 0 { 0 } else { genome(0).len() };
+        let locus_entropy = (0..loci)
+            .map(|locus| {
+                let mut counts: HashMap<u32, usize> = HashMap::new();
+                for index in 0..pop_size {
+                    *counts.entry(genome(index)[locus].gene).or_insert(0) += 1;
+                }
+
+                counts
+                    .values()
+                    .map(|&count| {
+                        let p = count as f32 / pop_size as f32;
+                        -p * p.log2()
+                    })
+                    .sum()
+            })
+            .collect();
+
+        Diversity {
+            genomes: pop_size,
+            unique_genomes: unique.len(),
+            mean_hamming,
+            locus_entropy,
+        }
+    }
+
+    //Fraction of genomes that are unique
+    pub fn get_unique_fraction(&self) -> f32 {
+        if self.genomes == 0 {
+            0.0
+        } else {
+            self.unique_genomes as f32 / self.genomes as f32
+        }
+    }
+
+    pub fn get_mean_entropy(&self) -> f32 {
+        if self.locus_entropy.is_empty() {
+            0.0
+        } else {
+            self.locus_entropy.iter().sum::<f32>() / self.locus_entropy.len() as f32
+        }
+    }
+}
+
+impl std::fmt::Display for Diversity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unique Genomes: {:3}\tMean Hamming Distance: {:.2}\tMean Locus Entropy: {:.3}",
+            self.unique_genomes,
+            self.mean_hamming,
+            self.get_mean_entropy()
+        )
+    }
+}
+
+//How the population's genomes use each node and connection
+pub struct ConnectionStats {
+    cells: usize,
+    inner_nodes: usize,
+    //Cells with at least one gene using the node as head or tail, by layout index
+    node_presence: Box<[usize]>,
+    //Per connection index: cells with a gene for it, and the sum of those cells' net weights
+    connection_presence: Box<[usize]>,
+    connection_weights: Box<[f32]>,
+    connection_nodes: Box<[Option<(NodeID, NodeID)>]>,
+}
+
+impl ConnectionStats {
+    pub fn collect(
+        population: &Population,
+        pop_size: usize,
+        inner_nodes: usize,
+    ) -> ConnectionStats {
+        let connections = NodeID::get_max_connections(inner_nodes);
+        let mut stats = ConnectionStats {
+            cells: pop_size,
+            inner_nodes,
+            node_presence: vec![0; NodeID::get_node_count(inner_nodes)].into_boxed_slice(),
+            connection_presence: vec![0; connections].into_boxed_slice(),
+            connection_weights: vec![0.0; connections].into_boxed_slice(),
+            connection_nodes: vec![None; connections].into_boxed_slice(),
+        };
+
+        //A cell's genes can repeat a node or connection, so they are gathered per cell first
+        let mut nodes = vec![false; NodeID::get_node_count(inner_nodes)];
+        let mut weights: Vec<Option<f32>> = vec![None; connections];
+
+        for index in 0..pop_size {
+            nodes.fill(false);
+            weights.fill(None);
+
+            for gene in population.getCellHeritableData(index).get_footer().iter() {
+                let (head, tail) = (gene.get_head_node_id(), gene.get_tail_node_id());
+                nodes[tail.get_layout_index(inner_nodes)] = true;
+
+                //Biases aren't connections, they only count towards the node they belong to
+                if gene.is_bias() {
+                    continue;
+                }
+
+                let connection = gene.get_connection_index(inner_nodes);
+                nodes[head.get_layout_index(inner_nodes)] = true;
+                *weights[connection].get_or_insert(0.0) += gene.get_weight();
+                stats.connection_nodes[connection].get_or_insert((head, tail));
+            }
+
+            for (node, used) in nodes.iter().enumerate() {
+                stats.node_presence[node] += *used as usize;
+            }
+            for (connection, weight) in weights.iter().enumerate() {
+                if let Some(weight) = weight {
+                    stats.connection_presence[connection] += 1;
+                    stats.connection_weights[connection] += weight;
+                }
+            }
+        }
+
+        stats
+    }
+
+    pub fn get_node_presence(&self) -> Vec<(NodeID, usize)> {
+        NodeID::get_nodes(self.inner_nodes)
+            .zip(self.node_presence.iter().copied())
+            .collect()
+    }
+
+    pub fn get_connection_presence(&self, connection: usize) -> usize {
+        self.connection_presence[connection]
+    }
+
+    //Mean net weight over the cells that have the connection
+    pub fn get_average_weight(&self, connection: usize) -> Option<f32> {
+        match self.connection_presence[connection] {
+            0 => None,
+            cells => Some(self.connection_weights[connection] / cells as f32),
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Neuron Frequencies ({} cells):", self.cells)?;
+        for (node, count) in self.get_node_presence() {
+            writeln!(f, "{:17}: {}", node, count)?;
+        }
+
+        let mut connections: Vec<usize> = (0..self.connection_presence.len())
+            .filter(|&connection| self.get_connection_presence(connection) > 0)
+            .collect();
+        connections
+            .sort_by_key(|&connection| std::cmp::Reverse(self.connection_presence[connection]));
+
+        writeln!(f, "Connections:")?;
+        for connection in connections {
+            let (head, tail) = self.connection_nodes[connection].unwrap();
+            writeln!(
+                f,
+                "{:17} -> {:11}: {:5} cells, average weight {:.3}",
+                head,
+                tail,
+                self.connection_presence[connection],
+                self.get_average_weight(connection).unwrap()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    Csv,
+    JsonLines,
+}
+
+impl StatsFormat {
+    //.jsonl and .json files get JSON Lines, everything else CSV
+    pub fn from_path(path: &str) -> StatsFormat {
+        if path.ends_with(".jsonl") || path.ends_with(".json") {
+            StatsFormat::JsonLines
+        } else {
+            StatsFormat::Csv
+        }
+    }
+}
+
+pub struct StatsWriter<W: Write> {
+    writer: W,
+    format: StatsFormat,
+    wrote_header: bool,
+}
+
+impl StatsWriter<BufWriter<File>> {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(StatsWriter::new(
+            BufWriter::new(File::create(path)?),
+            StatsFormat::from_path(path),
+        ))
+    }
+}
+
+impl<W: Write> StatsWriter<W> {
+    pub fn new(writer: W, format: StatsFormat) -> StatsWriter<W> {
+        StatsWriter {
+            writer,
+            format,
+            wrote_header: false,
+        }
+    }
+
+    //Flushed after every record so the file can be read while the simulation runs
+    pub fn write(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        match self.format {
+            StatsFormat::Csv => self.write_csv(stats)?,
+            StatsFormat::JsonLines => self.write_json(stats)?,
+        }
+
+        self.writer.flush()
+    }
+
+    fn write_csv(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        //The kill schedule can't change during a run, so the columns stay the same
+        if !self.wrote_header {
+            write!(self.writer, "generation")?;
+            for event in 0..stats.kill_deaths.len() {
+                write!(self.writer, ",kill_event_{}", event)?;
+            }
+            write!(self.writer, ",kill_forward,reproducers,survival_ratio,oscillator_mean,oscillator_std_dev,diversity,unique_genomes,mean_hamming,locus_entropy")?;
+            for (node, _) in &stats.node_presence {
+                write!(self.writer, ",node_{:?}", node)?;
+            }
+            writeln!(self.writer, ",seconds")?;
+
+            self.wrote_header = true;
+        }
+
+        write!(self.writer, "{}", stats.generation)?;
+        for deaths in &stats.kill_deaths {
+            write!(self.writer, ",{}", deaths)?;
+        }
+        write!(
+            self.writer,
+            ",{},{},{},{},{},{},{},{},{}",
+            stats.kill_forward_deaths,
+            stats.reproducers,
+            stats.survival_ratio,
+            stats.oscillator_mean,
+            stats.oscillator_std_dev,
+            stats.diversity.get_unique_fraction(),
+            stats.diversity.unique_genomes,
+            stats.diversity.mean_hamming,
+            stats.diversity.get_mean_entropy()
+        )?;
+        for (_, count) in &stats.node_presence {
+            write!(self.writer, ",{}", count)?;
+        }
+        writeln!(self.writer, ",{}", stats.seconds)
+    }
+
+    fn write_json(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        let kill_deaths: Vec<String> = stats
+            .kill_deaths
+            .iter()
+            .map(|deaths| deaths.to_string())
+            .collect();
+        let locus_entropy: Vec<String> = stats
+            .diversity
+            .locus_entropy
+            .iter()
+            .map(|entropy| entropy.to_string())
+            .collect();
+        let node_presence: Vec<String> = stats
+            .node_presence
+            .iter()
+            .map(|(node, count)| format!("\"{:?}\":{}", node, count))
+            .collect();
+
+        writeln!(
+            self.writer,
+            "{{\"generation\":{},\"kill_deaths\":[{}],\"kill_forward\":{},\"reproducers\":{},\"survival_ratio\":{},\"oscillator_mean\":{},\"oscillator_std_dev\":{},\"diversity\":{},\"unique_genomes\":{},\"mean_hamming\":{},\"locus_entropy\":[{}],\"node_presence\":{{{}}},\"seconds\":{}}}",
+            stats.generation,
+            kill_deaths.join(","),
+            stats.kill_forward_deaths,
+            stats.reproducers,
+            stats.survival_ratio,
+            stats.oscillator_mean,
+            stats.oscillator_std_dev,
+            stats.diversity.get_unique_fraction(),
+            stats.diversity.unique_genomes,
+            stats.diversity.mean_hamming,
+            locus_entropy.join(","),
+            node_presence.join(","),
+            stats.seconds
+        )
+    }
+
+    #[cfg(test)]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+//Writes the statistics of every generation, a failed write is reported and stops any further ones
+pub struct StatsObserver<W: Write> {
+    writer: Option<StatsWriter<W>>,
+}
+
+impl<W: Write> StatsObserver<W> {
+    pub fn new(writer: StatsWriter<W>) -> StatsObserver<W> {
+        StatsObserver {
+            writer: Some(writer),
+        }
+    }
+}
+
+impl<W: Write> Observer for StatsObserver<W> {
+    fn generation_end(&mut self, _context: &Context, report: &GenerationReport) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(err) = writer.write(&report.stats) {
+                println!("Error: Failed to write statistics, disabling them: {}", err);
+                self.writer = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{ConnectionStats, Diversity, GenerationStats, StatsFormat, StatsWriter};
+    use crate::{
+        config::Config,
+        grid::Grid,
+        population::Population,
+        rng::{derive_rng, Stream},
+    };
+
+    fn stats(generation: usize) -> GenerationStats {
+        let mut stats = GenerationStats::new(generation, 2);
+        stats.kill_deaths = vec![3, 4];
+        stats.reproducers = 10;
+        stats.survival_ratio = 0.5;
+        stats
+    }
+
+    #[test]
+    fn connection_counts() {
+        let config = Rc::new(Config::new(40, 6, 16, 16, 0.0, 30, false).unwrap());
+        let population = Population::new(
+            &config,
+            &mut Grid::new(16, 16),
+            &mut derive_rng(2, Stream::Genesis, 0, 0, 0),
+        );
+        let stats = ConnectionStats::collect(&population, 40, 3);
+
+        for (node, count) in stats.get_node_presence() {
+            let expected = (0..40)
+                .filter(|&index| {
+                    population
+                        .getCellHeritableData(index)
+                        .get_footer()
+                        .iter()
+                        .any(|gene| {
+                            gene.get_head_node_id() == node || gene.get_tail_node_id() == node
+                        })
+                })
+                .count();
+            assert_eq!(count, expected);
+        }
+
+        let genome = population.getCellHeritableData(0).get_footer();
+        let connection = genome
+            .iter()
+            .find(|gene| !gene.is_bias())
+            .unwrap()
+            .get_connection_index(3);
+        assert!(stats.get_connection_presence(connection) >= 1);
+        assert!(stats.get_average_weight(connection).is_some());
+
+        let total: usize = (0..crate::gene::NodeID::get_max_connections(3))
+            .map(|connection| stats.get_connection_presence(connection))
+            .sum();
+        assert!((40..=40 * 6).contains(&total));
+    }
+
+    #[test]
+    fn diversity_metrics() {
+        let config = Rc::new(Config::new(6, 4, 8, 8, 0.0, 30, false).unwrap());
+        let mut rng = derive_rng(3, Stream::Genesis, 0, 0, 0);
+        let population = Population::new(&config, &mut Grid::new(8, 8), &mut rng);
+        let genomes = population.get_heritable_data();
+
+        let diversity = Diversity::collect(genomes, 6, &mut rng);
+        assert_eq!(diversity.unique_genomes, 6);
+        assert_eq!(diversity.locus_entropy.len(), 4);
+        for entropy in &diversity.locus_entropy {
+            assert!(*entropy > 0.0 && *entropy <= (6f32).log2() + 1e-5);
+        }
+
+        //Few enough pairs that every pair is compared
+        let mut total = 0;
+        for first in 0..6 {
+            for second in (first + 1)..6 {
+                let (a, b) = (
+                    genomes.get_arr_element(first).get_footer(),
+                    genomes.get_arr_element(second).get_footer(),
+                );
+                total += (0..4)
+                    .map(|locus| (a[locus].gene ^ b[locus].gene).count_ones())
+                    .sum::<u32>();
+            }
+        }
+        assert!((diversity.mean_hamming - total as f32 / 15.0).abs() < 1e-4);
+
+        let single = Diversity::collect(genomes, 1, &mut rng);
+        assert_eq!(single.mean_hamming, 0.0);
+        assert_eq!(single.get_mean_entropy(), 0.0);
+        assert_eq!(single.get_unique_fraction(), 1.0);
+    }
+
+    #[test]
+    fn csv_and_json_lines() {
+        let mut csv = StatsWriter::new(Vec::new(), StatsFormat::Csv);
+        csv.write(&stats(0)).unwrap();
+        csv.write(&stats(1)).unwrap();
+
+        let csv = String::from_utf8(csv.into_inner()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("generation,kill_event_0,kill_event_1,kill_forward,"));
+        assert!(lines[2].starts_with("1,3,4,0,10,0.5,"));
+        assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+
+        let mut json = StatsWriter::new(Vec::new(), StatsFormat::JsonLines);
+        json.write(&stats(7)).unwrap();
+
+        let json = String::from_utf8(json.into_inner()).unwrap();
+        assert!(json.starts_with("{\"generation\":7,\"kill_deaths\":[3,4],\"kill_forward\":0,"));
+        assert!(json.ends_with("}\n"));
+
+        assert_eq!(StatsFormat::from_path("run.jsonl"), StatsFormat::JsonLines);
+        assert_eq!(StatsFormat::from_path("run.csv"), StatsFormat::Csv);
+    }
+}
@@ -5,4 +5,6 @@ pub mod shader;
 pub struct WindowingStatus {
     pub is_paused: bool,
     pub should_reset: bool,
+    pub should_save: bool,
+    pub should_print_connections: bool,
 }
@@ -10,9 +10,10 @@ use crate::Config;
 
 extern crate glfw;
 
+use crate::grid::Grid;
 use crate::population::Population;
+use crate::windowed::shader::Shader;
 use crate::DebugCell::DebugRefCell;
-use crate::{gene::NodeID, neuron_presence, windowed::shader::Shader};
 
 use super::WindowingStatus;
 
@@ -155,7 +156,7 @@ impl Window {
         self.cell_VAO = VAO;
     }
 
-    pub fn render(&self, config: &Config, population: &Population) {
+    pub fn render(&self, config: &Config, grid: &Grid, population: &Population) {
         unsafe {
             gl::ClearColor(0.0, 0.0, 0.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
@@ -198,7 +199,15 @@ impl Window {
                     living
                 };
 
-                let len = livingCells.len();
+                let barriers = grid.get_barrier_coords();
+
+                let len = livingCells.len() + barriers.len();
+
+                for (x, y) in barriers {
+                    buffer.push((x as f32) / (config.get_grid_width() as f32) * 2.0 - 1.0);
+                    buffer.push(((y + 1) as f32) / (config.get_grid_height() as f32) * 2.0 - 1.0);
+                    buffer.extend_from_slice(&[0.35, 0.35, 0.35]);
+                }
 
                 for (movement, other) in livingCells {
                     buffer.push(
@@ -329,16 +338,13 @@ extern "C" fn keyCallback(
         let paused = !unsafe { (*ptr).1.borrow().is_paused };
         unsafe { (*ptr).1.borrowMut().is_paused = paused };
     } else if key == glfw::ffi::KEY_E && action == glfw::ffi::PRESS {
-        print!("\nNeuron Frequencies: \n");
-        unsafe {
-            for (index, count) in neuron_presence.iter().enumerate() {
-                println!("{}: {}", NodeID::from_index(index), *count);
-            }
-            println!();
-        }
+        let ptr = unsafe { get_window_user_ptr(window) };
+        unsafe { (*ptr).1.borrowMut().should_print_connections = true };
     } else if key == glfw::ffi::KEY_ESCAPE {
         unsafe { glfw::ffi::glfwSetWindowShouldClose(window, glfw::ffi::TRUE) };
     } else if key == glfw::ffi::KEY_S && action == glfw::ffi::PRESS {
+        let ptr = unsafe { get_window_user_ptr(window) };
+        unsafe { (*ptr).1.borrowMut().should_save = true };
     } else if key == glfw::ffi::KEY_C && action == glfw::ffi::PRESS {
         let ptr = unsafe { get_window_user_ptr(window) };
 
@@ -380,3 +386,18 @@ pub fn wait(
     }
     *accounted_time += secs;
 }
+
+//Keeps the window responsive until it is closed or the simulation is reset
+pub fn wait_for_reset(
+    window: &Window,
+    windowing_status: &Rc<DebugRefCell<WindowingStatus>>,
+    accounted_time: &mut f64,
+) {
+    loop {
+        window.poll();
+        if window.shouldClose() || windowing_status.borrow().should_reset {
+            unsafe { *accounted_time = glfw::ffi::glfwGetTime() };
+            break;
+        }
+    }
+}